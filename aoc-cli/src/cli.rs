@@ -34,9 +34,25 @@ use restroom_redoubt::RestroomRedoubt;
 use warehouse_woes::WarehouseWoes;
 // import_marker
 
+use crate::extras::{
+    BridgeRepairCmd, GardenGroupsCmd, GuardGallivantCmd, ReindeerMazeCmd, RestroomRedoubtCmd,
+    WarehouseWoesCmd,
+};
+
+// Days with extra, day-specific flags supply their own command type in place
+// of the default `Solver`.
+macro_rules! solver_type {
+    ($name:ident) => {
+        Solver<$name>
+    };
+    ($name:ident, $cmd:ty) => {
+        $cmd
+    };
+}
+
 // I'm not proud
 macro_rules! generate_cli {
-    ($(($name:ident, $day:literal)),* $(,)?) => {
+    ($(($name:ident, $day:literal $(, $cmd:ty)?)),* $(,)?) => {
         /// Advent of Code solutions for 2024
         #[derive(Parser)]
         #[command(name = "aoc", author, version)]
@@ -59,7 +75,7 @@ macro_rules! generate_cli {
         pub(crate) enum Commands {
             $(
             #[command(about = $name::problem_label(), long_about = $name::long_description(), display_order = $day)]
-            $name(Solver<$name>),
+            $name(solver_type!($name $(, $cmd)?)),
             )*
 
             #[command(display_order = 30)]
@@ -137,11 +153,11 @@ where
     T: Problem,
 {
     /// The path to the input for this solution.
-    input: PathBuf,
+    pub input: PathBuf,
 
     /// Display the output as json.
     #[clap(short, long)]
-    json: bool,
+    pub json: bool,
 
     #[clap(skip)]
     _phantom: PhantomData<T>,
@@ -194,17 +210,17 @@ generate_cli! {
     (MullItOver, 3),
     (CeresSearch, 4),
     (PrintQueue, 5),
-    (GuardGallivant, 6),
-    (BridgeRepair, 7),
+    (GuardGallivant, 6, GuardGallivantCmd),
+    (BridgeRepair, 7, BridgeRepairCmd),
     (ResonantCollinearity, 8),
     (DiskFragmenter, 9),
     (HoofIt, 10),
     (PlutoniumPebbles, 11),
-    (GardenGroups, 12),
+    (GardenGroups, 12, GardenGroupsCmd),
     (ClawContraption, 13),
-    (RestroomRedoubt, 14),
-    (WarehouseWoes, 15),
-    (ReindeerMaze, 16),
+    (RestroomRedoubt, 14, RestroomRedoubtCmd),
+    (WarehouseWoes, 15, WarehouseWoesCmd),
+    (ReindeerMaze, 16, ReindeerMazeCmd),
     (ChronospatialComputer, 17),
     (RamRun, 18),
     (LinenLayout, 19),
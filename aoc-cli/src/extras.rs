@@ -0,0 +1,286 @@
+//! Day-specific commands that expose more than just the solution.
+use anyhow::{Context, Result};
+use aoc_plumbing::{Problem, Solution};
+use bridge_repair::{BridgeRepair, PART_ONE_OPERATORS, PART_TWO_OPERATORS};
+use clap::Args;
+use garden_groups::GardenGroups;
+use guard_gallivant::GuardGallivant;
+use reindeer_maze::ReindeerMaze;
+use restroom_redoubt::RestroomRedoubt;
+use warehouse_woes::{Warehouse, WarehouseWoes};
+
+use crate::cli::Solver;
+
+fn load<T>(solver: &Solver<T>) -> Result<T>
+where
+    T: Problem,
+    <T as Problem>::ProblemError: Into<anyhow::Error>,
+    <T as std::str::FromStr>::Err: Into<anyhow::Error>,
+{
+    let input = std::fs::read_to_string(&solver.input).context("Could not read input file")?;
+    T::instance(&input)
+        .map_err(Into::<anyhow::Error>::into)
+        .context("Failed to parse input")
+}
+
+#[derive(Args)]
+pub(crate) struct GuardGallivantCmd {
+    #[command(flatten)]
+    solver: Solver<GuardGallivant>,
+
+    /// Print the map with the guard's path and loop-producing obstructions.
+    #[clap(long)]
+    render: bool,
+}
+
+impl GuardGallivantCmd {
+    pub fn run(&self) -> Result<()> {
+        if !self.render {
+            return self.solver.run();
+        }
+
+        let inst = load(&self.solver)?;
+        print!("{}", inst.render());
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub(crate) struct BridgeRepairCmd {
+    #[command(flatten)]
+    solver: Solver<BridgeRepair>,
+
+    /// Print the operators that make each valid equation true.
+    #[clap(long)]
+    explain: bool,
+
+    /// With --explain, print every solution and the number of solutions.
+    #[clap(long, requires = "explain")]
+    all: bool,
+
+    /// With --explain, only allow the `+` and `*` operators.
+    #[clap(long, requires = "explain")]
+    no_concat: bool,
+}
+
+impl BridgeRepairCmd {
+    pub fn run(&self) -> Result<()> {
+        if !self.explain {
+            return self.solver.run();
+        }
+
+        let inst = load(&self.solver)?;
+        let operators = if self.no_concat {
+            PART_ONE_OPERATORS
+        } else {
+            PART_TWO_OPERATORS
+        };
+
+        for eq in inst.equations() {
+            if self.all {
+                let solutions = eq.solutions(operators);
+                if solutions.is_empty() {
+                    continue;
+                }
+
+                let plural = if solutions.len() == 1 { "" } else { "s" };
+                println!("{} ({} solution{})", eq.target(), solutions.len(), plural);
+                for solution in solutions {
+                    println!("  = {}", solution);
+                }
+            } else if let Some(solution) = eq.solve(operators) {
+                println!("{} = {}", eq.target(), solution);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub(crate) struct GardenGroupsCmd {
+    #[command(flatten)]
+    solver: Solver<GardenGroups>,
+
+    /// Print a table of every region.
+    #[clap(long)]
+    regions: bool,
+}
+
+impl GardenGroupsCmd {
+    pub fn run(&self) -> Result<()> {
+        if !self.regions {
+            return self.solver.run();
+        }
+
+        let inst = load(&self.solver)?;
+        println!(
+            "{:>6} {:>5} {:>6} {:>9} {:>6} {:>21} {:>5} {:>8}",
+            "id", "label", "area", "perimeter", "sides", "bounds", "holes", "enclosed"
+        );
+        for region in inst.regions().iter() {
+            let (min, max) = region.bounds;
+            let bounds = format!("({},{})-({},{})", min.row, min.col, max.row, max.col);
+            let enclosed = region
+                .enclosed_by
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            println!(
+                "{:>6} {:>5} {:>6} {:>9} {:>6} {:>21} {:>5} {:>8}",
+                region.id,
+                region.label,
+                region.area,
+                region.perimeter,
+                region.sides,
+                bounds,
+                region.holes,
+                enclosed
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub(crate) struct ReindeerMazeCmd {
+    #[command(flatten)]
+    solver: Solver<ReindeerMaze>,
+
+    /// Print the maze with every tile on a best path marked with an `O`.
+    #[clap(long)]
+    render: bool,
+
+    /// Print every best path as a list of (row, col) tiles.
+    #[clap(long, conflicts_with = "render")]
+    paths: bool,
+
+    /// Print the collapsed junction graph in Graphviz DOT format.
+    #[clap(long, conflicts_with_all = ["render", "paths"])]
+    dot: bool,
+}
+
+impl ReindeerMazeCmd {
+    pub fn run(&self) -> Result<()> {
+        if !(self.render || self.paths || self.dot) {
+            return self.solver.run();
+        }
+
+        let inst = load(&self.solver)?;
+        if self.render {
+            print!("{}", inst.render());
+        } else if self.paths {
+            for path in inst.best_paths() {
+                let tiles: Vec<_> = path
+                    .iter()
+                    .map(|l| format!("({}, {})", l.row, l.col))
+                    .collect();
+                println!("{}", tiles.join(" "));
+            }
+        } else {
+            print!("{}", inst.graph().to_dot());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub(crate) struct RestroomRedoubtCmd {
+    #[command(flatten)]
+    solver: Solver<RestroomRedoubt>,
+
+    /// The width of the room, overriding any size in the input.
+    #[clap(long, requires = "height")]
+    width: Option<i32>,
+
+    /// The height of the room, overriding any size in the input.
+    #[clap(long, requires = "width")]
+    height: Option<i32>,
+}
+
+impl RestroomRedoubtCmd {
+    pub fn run(&self) -> Result<()> {
+        let (Some(width), Some(height)) = (self.width, self.height) else {
+            return self.solver.run();
+        };
+
+        let mut inst = load(&self.solver)?.with_dimensions(width, height)?;
+        let solution = Solution::new(inst.part_one()?, inst.part_two()?);
+
+        if self.solver.json {
+            println!("{}", serde_json::to_string(&solution)?);
+        } else {
+            println!("{}", solution);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub(crate) struct WarehouseWoesCmd {
+    #[command(flatten)]
+    solver: Solver<WarehouseWoes>,
+
+    /// Print the warehouse after every movement.
+    #[clap(long)]
+    replay: bool,
+
+    /// With --replay, use the doubled-width warehouse.
+    #[clap(long, requires = "replay")]
+    wide: bool,
+
+    /// With --replay, make everything this many times as wide.
+    #[clap(long, requires = "replay", conflicts_with = "wide")]
+    scale: Option<usize>,
+
+    /// With --replay, list the boxes pushed by each movement.
+    #[clap(long, requires = "replay")]
+    diff: bool,
+
+    /// Find the fewest pushes, then movements, that get every box onto a
+    /// target (`T`). Any movements in the input are ignored.
+    #[clap(long, conflicts_with = "replay")]
+    solve: bool,
+}
+
+impl WarehouseWoesCmd {
+    pub fn run(&self) -> Result<()> {
+        if self.solve {
+            let input =
+                std::fs::read_to_string(&self.solver.input).context("Could not read input file")?;
+            let map = input.trim().split("\n\n").next().unwrap_or_default();
+            let warehouse: Warehouse = map.parse().context("Failed to parse input")?;
+            match warehouse.solve()? {
+                Some(moves) => println!("{} ({} movements)", moves, moves.len()),
+                None => println!("unsolvable"),
+            }
+            return Ok(());
+        }
+
+        if !self.replay {
+            return self.solver.run();
+        }
+
+        let inst = load(&self.solver)?;
+        let sim = match self.scale {
+            Some(factor) => inst.simulate_scaled(factor)?,
+            None if self.wide => inst.simulate_wide(),
+            None => inst.simulate(),
+        };
+
+        let mut gps = None;
+        for step in sim {
+            println!("{}", step.render(self.diff));
+            gps = Some(step.warehouse.gps());
+        }
+
+        if let Some(gps) = gps {
+            println!("GPS sum: {}", gps);
+        }
+
+        Ok(())
+    }
+}
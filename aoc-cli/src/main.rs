@@ -1,6 +1,9 @@
 #[cfg(feature = "clap")]
 mod cli;
 
+#[cfg(feature = "clap")]
+mod extras;
+
 #[cfg(feature = "fast")]
 mod fast;
 
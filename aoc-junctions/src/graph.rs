@@ -0,0 +1,654 @@
+use aoc_std::{collections::CharGrid, directions::Cardinal, geometry::Location};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// Every facing, in `facing_idx` order.
+pub(crate) const FACINGS: [Cardinal; 4] = [
+    Cardinal::North,
+    Cardinal::East,
+    Cardinal::South,
+    Cardinal::West,
+];
+
+pub(crate) fn facing_idx(facing: Cardinal) -> usize {
+    match facing {
+        Cardinal::North => 0,
+        Cardinal::East => 1,
+        Cardinal::South => 2,
+        Cardinal::West => 3,
+    }
+}
+
+/// What it costs to move around the maze.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Costs {
+    pub step: usize,
+    pub turn: usize,
+}
+
+impl Costs {
+    /// Every step costs the same and turning is free, so the cheapest path is
+    /// the shortest one.
+    pub const UNIFORM: Self = Self { step: 1, turn: 0 };
+
+    /// The cost of rotating from `from` to `to`, a quarter turn at a time.
+    pub fn turn_cost(&self, from: Cardinal, to: Cardinal) -> usize {
+        if from == to {
+            0
+        } else if from.opposite() == to {
+            2 * self.turn
+        } else {
+            self.turn
+        }
+    }
+
+    /// The cost of taking a step towards `to` when facing `from`.
+    pub fn move_cost(&self, from: Cardinal, to: Cardinal) -> usize {
+        self.step + self.turn_cost(from, to)
+    }
+}
+
+/// A maze collapsed down to its junctions, with an edge for each corridor
+/// between them.
+#[derive(Debug, Clone)]
+pub struct Graph {
+    node_map: FxHashMap<Location, usize>,
+    nodes: Vec<GraphNode>,
+    /// The tiles between the junctions at either end of an edge. Collapsing
+    /// can leave an edge with more than one equally good way through.
+    routes: Vec<Vec<Vec<Location>>>,
+    keep: usize,
+    costs: Costs,
+}
+
+impl Graph {
+    /// Collapse the maze down to just the junctions. The `keep` locations
+    /// become nodes `0..keep.len()`, in order, and are never collapsed away.
+    /// They need to be distinct floor tiles.
+    ///
+    /// Anything that can't be on a cheapest path between junctions is pruned,
+    /// so every node other than the kept ones might end up without any edges.
+    pub fn build(grid: &CharGrid, keep: &[Location], costs: Costs) -> Self {
+        let mut graph = Graph {
+            node_map: FxHashMap::default(),
+            nodes: Vec::default(),
+            routes: Vec::default(),
+            keep: keep.len(),
+            costs,
+        };
+
+        for loc in keep.iter() {
+            graph.insert_node(*loc, grid.locations[loc.row][loc.col]);
+        }
+
+        // let's collapse the grid to just the junctions
+        for r in 0..grid.height() {
+            for c in 0..grid.width() {
+                let loc = Location::new(r, c);
+                if grid.locations[r][c] != '#'
+                    && !graph.node_map.contains_key(&loc)
+                    && grid
+                        .cardinal_neighbors(&loc)
+                        .filter(|(_, _, v)| **v != '#')
+                        .count()
+                        > 2
+                {
+                    graph.insert_node(loc, grid.locations[r][c]);
+                }
+            }
+        }
+
+        // The idea is to collapse the grid into a graph where we pre-compute
+        // the costs between junctions (and the kept locations as junctions).
+        //
+        // This will hopefully make the resulting search faster because it
+        // doesn't have to examine as many nodes.
+
+        // walk all the nodes to their closest neighbors in each direction
+        for i in 0..graph.nodes.len() {
+            walk_corridors(i, grid, &mut graph);
+        }
+
+        // cool, now let's remove always-bad edges, nodes that only have 2 edges
+        // that are not kept nodes, and let's remove edges that lead to a dead
+        // end node.
+        //
+        // make two passes
+        for _ in 0..2 {
+            // skip the kept nodes
+            for i in (graph.keep..graph.nodes.len()).rev() {
+                // remove dead-end nodes
+                if graph.nodes[i].edges.len() < 2 {
+                    remove_single_edge_nodes(&mut graph, i);
+                    continue;
+                }
+
+                // remove multiple paths to same destination, if one is always
+                // going to be cheaper than the other
+                if graph.nodes[i].edges.len() > 2 {
+                    'outer: for j in 0..graph.nodes[i].edges.len() {
+                        for k in (j + 1)..graph.nodes[i].edges.len() {
+                            if graph.nodes[i].edges[j].to == graph.nodes[i].edges[k].to {
+                                let left = graph.nodes[i].edges[j];
+                                let right = graph.nodes[i].edges[k];
+
+                                if left.dominates(&right, &costs) {
+                                    graph.nodes[i].edges.remove(k);
+                                    graph.nodes[right.to].edges.retain(|e| {
+                                        e.to != i || e.exit_dir != right.enter_dir.opposite()
+                                    });
+                                    break 'outer;
+                                } else if right.dominates(&left, &costs) {
+                                    graph.nodes[i].edges.remove(j);
+                                    graph.nodes[left.to].edges.retain(|e| {
+                                        e.to != i || e.exit_dir != left.enter_dir.opposite()
+                                    });
+                                    break 'outer;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // remove join the edges of nodes that are effectively corridors
+                if graph.nodes[i].edges.len() == 2 {
+                    let left = graph.nodes[i].edges[0];
+                    let right = graph.nodes[i].edges[1];
+                    graph.nodes[i].edges.clear();
+
+                    // a loop back to the same node is never worth taking
+                    if left.to == right.to {
+                        graph.nodes[left.to].edges.retain(|e| e.to != i);
+                        continue;
+                    }
+
+                    // we want to join these edges, so we need to know how much
+                    // it costs to move through it
+                    let traverse_cost = costs.move_cost(left.enter_dir.opposite(), right.enter_dir);
+
+                    let cost = left.cost + right.cost + traverse_cost;
+
+                    // the new distance includes the junction we're removing
+                    let dist = left.distance + right.distance + 1;
+
+                    let there = chain(
+                        &[
+                            &reversed(&graph.routes[left.route]),
+                            &graph.routes[right.route],
+                        ],
+                        &[graph.nodes[i].location],
+                    );
+                    let back = graph.insert_route(reversed(&there));
+                    let there = graph.insert_route(there);
+
+                    for e in graph.nodes[left.to].edges.iter_mut() {
+                        if e.to == i {
+                            e.to = right.to;
+                            e.exit_dir = right.exit_dir;
+                            e.distance = dist;
+                            e.cost = cost;
+                            e.route = there;
+                            break;
+                        }
+                    }
+
+                    for e in graph.nodes[right.to].edges.iter_mut() {
+                        if e.to == i {
+                            e.to = left.to;
+                            e.exit_dir = left.exit_dir;
+                            e.distance = dist;
+                            e.cost = cost;
+                            e.route = back;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        // we might have situations (or a variant of this)
+        //       +-------+
+        //       |       |
+        // X --- A ----- B ---- Y
+        //
+        // we can collapse all of these into X -- Y by computing the cheapest
+        // cost/distance through the junctions A, B
+        for i in graph.keep..graph.nodes.len() {
+            if graph.nodes[i].edges.len() == 3 {
+                if graph.nodes[i].edges[0].to == graph.nodes[i].edges[1].to {
+                    collapse_forked_rejoin(&mut graph, i, 0, 1, 2);
+                    continue;
+                }
+
+                if graph.nodes[i].edges[0].to == graph.nodes[i].edges[2].to {
+                    collapse_forked_rejoin(&mut graph, i, 0, 2, 1);
+                    continue;
+                }
+
+                if graph.nodes[i].edges[1].to == graph.nodes[i].edges[2].to {
+                    collapse_forked_rejoin(&mut graph, i, 1, 2, 0);
+                    continue;
+                }
+            }
+        }
+
+        graph
+    }
+
+    fn insert_node(&mut self, location: Location, tile: char) {
+        let id = self.nodes.len();
+        self.nodes.push(GraphNode {
+            location,
+            tile,
+            edges: Vec::default(),
+        });
+        self.node_map.insert(location, id);
+    }
+
+    fn insert_route(&mut self, route: Vec<Vec<Location>>) -> usize {
+        self.routes.push(route);
+        self.routes.len() - 1
+    }
+
+    /// Every node, including the ones that were collapsed away.
+    pub fn nodes(&self) -> &[GraphNode] {
+        &self.nodes
+    }
+
+    pub fn node(&self, idx: usize) -> &GraphNode {
+        &self.nodes[idx]
+    }
+
+    /// The node at `location`, if there is one.
+    pub fn node_at(&self, location: &Location) -> Option<usize> {
+        self.node_map.get(location).copied()
+    }
+
+    /// How many nodes were kept, which are always the first ones.
+    pub fn kept(&self) -> usize {
+        self.keep
+    }
+
+    pub fn costs(&self) -> &Costs {
+        &self.costs
+    }
+
+    /// Every way through the tiles between the two ends of `edge`, not
+    /// counting the ends themselves.
+    pub fn routes(&self, edge: &Edge) -> &[Vec<Location>] {
+        &self.routes[edge.route]
+    }
+
+    /// How many different tiles are on any of the routes of `edge`.
+    pub fn route_tiles(&self, edge: &Edge) -> usize {
+        self.routes(edge)
+            .iter()
+            .flatten()
+            .collect::<FxHashSet<_>>()
+            .len()
+    }
+
+    /// The junctions and the corridors between them in Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("graph junctions {\n");
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let name = if i < self.keep {
+                format!("{} ", node.tile)
+            } else if node.edges.is_empty() {
+                continue;
+            } else {
+                String::new()
+            };
+            out.push_str(&format!(
+                "    n{} [label=\"{}({}, {})\"];\n",
+                i, name, node.location.row, node.location.col
+            ));
+        }
+
+        // both directions of an edge cost the same, so only list one
+        for edge in self.nodes.iter().flat_map(|n| n.edges.iter()) {
+            if edge.from < edge.to {
+                out.push_str(&format!(
+                    "    n{} -- n{} [label=\"{} ({} tiles)\"];\n",
+                    edge.from, edge.to, edge.cost, edge.distance
+                ));
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphNode {
+    pub location: Location,
+    /// What was on the grid here.
+    pub tile: char,
+    pub edges: Vec<Edge>,
+}
+
+/// One way along a corridor. The way back is a separate edge on the node at
+/// the other end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    /// The way we're facing after the first step out of `from`.
+    pub enter_dir: Cardinal,
+    /// The way we're facing after the last step into `to`.
+    pub exit_dir: Cardinal,
+    /// How many tiles are between `from` and `to` on the shortest of the
+    /// routes. [`Graph::route_tiles`] counts the tiles on all of them.
+    pub distance: usize,
+    /// The cost of everything after the first step out of `from`, which
+    /// depends on how we were facing.
+    pub cost: usize,
+    route: usize,
+}
+
+impl Edge {
+    /// Whether this edge is always cheaper than `other` between the same two
+    /// junctions, no matter which way we arrive or leave.
+    pub fn dominates(&self, other: &Edge, costs: &Costs) -> bool {
+        let worst_enter = FACINGS
+            .iter()
+            .map(|f| {
+                costs.turn_cost(*f, self.enter_dir) as isize
+                    - costs.turn_cost(*f, other.enter_dir) as isize
+            })
+            .max()
+            .unwrap_or_default();
+        let worst_exit = FACINGS
+            .iter()
+            .map(|f| {
+                costs.turn_cost(self.exit_dir, *f) as isize
+                    - costs.turn_cost(other.exit_dir, *f) as isize
+            })
+            .max()
+            .unwrap_or_default();
+
+        (self.cost as isize) + worst_enter + worst_exit < other.cost as isize
+    }
+}
+
+/// Follow each corridor out of junction `idx` until it reaches another
+/// junction, adding an edge for every one that does.
+fn walk_corridors(idx: usize, grid: &CharGrid, graph: &mut Graph) {
+    let start = graph.nodes[idx].location;
+
+    for (enter_dir, first, _) in grid
+        .cardinal_neighbors(&start)
+        .filter(|(_, _, v)| **v != '#')
+    {
+        let mut tiles = Vec::new();
+        let mut facing = enter_dir;
+        let mut loc = first;
+        let mut cost = 0;
+
+        // corridors can't fork, since anywhere that does is a junction
+        let other = loop {
+            if let Some(other) = graph.node_map.get(&loc).copied() {
+                break Some(other);
+            }
+
+            tiles.push(loc);
+
+            let Some((d, l, _)) = grid
+                .cardinal_neighbors(&loc)
+                .find(|(d, _, v)| **v != '#' && d.opposite() != facing)
+            else {
+                break None;
+            };
+
+            cost += graph.costs.move_cost(facing, d);
+            facing = d;
+            loc = l;
+        };
+
+        // dead ends and loops back to where we started are never worth taking
+        let Some(other) = other.filter(|o| *o != idx) else {
+            continue;
+        };
+
+        let distance = tiles.len();
+        let route = graph.insert_route(vec![tiles]);
+
+        graph.nodes[idx].edges.push(Edge {
+            from: idx,
+            to: other,
+            enter_dir,
+            exit_dir: facing,
+            distance,
+            cost,
+            route,
+        });
+    }
+}
+
+fn remove_single_edge_nodes(graph: &mut Graph, i: usize) {
+    // never remove the kept nodes
+    if i >= graph.keep && graph.nodes[i].edges.len() == 1 {
+        let to = graph.nodes[i].edges[0].to;
+        graph.nodes[to].edges.retain(|e| e.to != i);
+        graph.nodes[i].edges.clear();
+        remove_single_edge_nodes(graph, to);
+    }
+}
+
+fn collapse_forked_rejoin(graph: &mut Graph, i: usize, up_i: usize, dn_i: usize, r_i: usize) {
+    let costs = graph.costs;
+    let other = graph.nodes[i].edges[up_i].to;
+
+    // never remove the kept nodes
+    if other < graph.keep || graph.nodes[other].edges.len() != 3 {
+        return;
+    }
+
+    if let Some(rem_right) = graph.nodes[other].edges.iter().find(|e| e.to != i).copied() {
+        let rem_left = graph.nodes[i].edges[r_i];
+        // okay, we're going to join the unique node that we have
+        // with the unique node the other has, bypassing both
+        // us and the unique node
+        let up = graph.nodes[i].edges[up_i];
+        let dn = graph.nodes[i].edges[dn_i];
+
+        let cost_up = rem_left.cost
+            + rem_right.cost
+            + up.cost
+            + costs.move_cost(rem_left.enter_dir.opposite(), up.enter_dir)
+            + costs.move_cost(up.exit_dir, rem_right.enter_dir);
+
+        let cost_dn = rem_left.cost
+            + rem_right.cost
+            + dn.cost
+            + costs.move_cost(rem_left.enter_dir.opposite(), dn.enter_dir)
+            + costs.move_cost(dn.exit_dir, rem_right.enter_dir);
+
+        let dist_up = rem_left.distance + rem_right.distance + up.distance + 2;
+        let dist_dn = rem_left.distance + rem_right.distance + dn.distance + 2;
+
+        #[allow(clippy::comparison_chain)]
+        let (final_cost, final_dist, branches) = if cost_up == cost_dn {
+            // both branches, which can differ in length if one has fewer turns
+            (cost_up, dist_up.min(dist_dn), vec![up.route, dn.route])
+        } else if cost_up < cost_dn {
+            (cost_up, dist_up, vec![up.route])
+        } else {
+            (cost_dn, dist_dn, vec![dn.route])
+        };
+
+        let branch: Vec<Vec<Location>> = branches
+            .iter()
+            .flat_map(|r| graph.routes[*r].iter().cloned())
+            .collect();
+        let there = chain(
+            &[
+                &reversed(&graph.routes[rem_left.route]),
+                &branch,
+                &graph.routes[rem_right.route],
+            ],
+            &[graph.nodes[i].location, graph.nodes[other].location],
+        );
+        let back = graph.insert_route(reversed(&there));
+        let there = graph.insert_route(there);
+
+        for e in graph.nodes[rem_left.to].edges.iter_mut() {
+            if e.to == i {
+                e.to = rem_right.to;
+                e.exit_dir = rem_right.exit_dir;
+                e.distance = final_dist;
+                e.cost = final_cost;
+                e.route = there;
+                break;
+            }
+        }
+
+        for e in graph.nodes[rem_right.to].edges.iter_mut() {
+            if e.to == other {
+                e.to = rem_left.to;
+                e.exit_dir = rem_left.exit_dir;
+                e.distance = final_dist;
+                e.cost = final_cost;
+                e.route = back;
+                break;
+            }
+        }
+        // remove ourselves from the valid nodes
+        graph.nodes[i].edges.clear();
+        graph.nodes[other].edges.clear();
+    }
+}
+
+/// Every way through `parts` one after the other, with the `joints` tiles in
+/// between.
+fn chain(parts: &[&[Vec<Location>]], joints: &[Location]) -> Vec<Vec<Location>> {
+    let mut out = vec![Vec::new()];
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            for route in out.iter_mut() {
+                route.push(joints[i - 1]);
+            }
+        }
+
+        out = out
+            .iter()
+            .flat_map(|prefix| {
+                part.iter().map(move |alt| {
+                    let mut route = prefix.clone();
+                    route.extend_from_slice(alt);
+                    route
+                })
+            })
+            .collect();
+    }
+
+    out
+}
+
+fn reversed(routes: &[Vec<Location>]) -> Vec<Vec<Location>> {
+    routes
+        .iter()
+        .map(|r| r.iter().rev().copied().collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn collapses_corridors() {
+        // the junction at (1, 3) only leads to a dead end, so it's just
+        // part of the corridor from S to E
+        let grid = CharGrid::from_str("#######\n#S...E#\n###.###\n###.###\n#######").unwrap();
+        let start = Location::new(1, 1);
+        let end = Location::new(1, 5);
+        let graph = Graph::build(&grid, &[start, end], Costs::UNIFORM);
+
+        assert_eq!(graph.node_at(&start), Some(0));
+        assert_eq!(graph.node_at(&end), Some(1));
+        assert_eq!(graph.node_at(&Location::new(1, 3)), Some(2));
+        assert!(graph.node(2).edges.is_empty());
+
+        assert_eq!(graph.node(0).edges.len(), 1);
+        let edge = graph.node(0).edges[0];
+        assert_eq!((edge.to, edge.distance, edge.cost), (1, 3, 3));
+        assert_eq!(
+            (edge.enter_dir, edge.exit_dir),
+            (Cardinal::East, Cardinal::East)
+        );
+        assert_eq!(
+            graph.routes(&edge),
+            &[vec![
+                Location::new(1, 2),
+                Location::new(1, 3),
+                Location::new(1, 4)
+            ]]
+        );
+
+        let back = graph.node(1).edges[0];
+        assert_eq!(back.to, 0);
+        assert_eq!(graph.routes(&back)[0][0], Location::new(1, 4));
+
+        assert_eq!(
+            graph.to_dot(),
+            "graph junctions {
+    n0 [label=\"S (1, 1)\"];
+    n1 [label=\"E (1, 5)\"];
+    n0 -- n1 [label=\"3 (3 tiles)\"];
+}
+"
+        );
+    }
+
+    #[test]
+    fn turns() {
+        let grid = CharGrid::from_str("#####\n#S..#\n#.#.#\n#..E#\n#####").unwrap();
+        let costs = Costs {
+            step: 1,
+            turn: 1000,
+        };
+        let graph = Graph::build(&grid, &[Location::new(1, 1), Location::new(3, 3)], costs);
+
+        // both ways around are kept, since it depends which way we start out
+        let mut edges = graph.node(0).edges.clone();
+        edges.sort();
+        assert_eq!(edges.len(), 2);
+        assert_eq!(
+            (edges[0].enter_dir, edges[0].exit_dir, edges[0].cost),
+            (Cardinal::East, Cardinal::South, 1003)
+        );
+        assert_eq!(
+            (edges[1].enter_dir, edges[1].exit_dir, edges[1].cost),
+            (Cardinal::South, Cardinal::East, 1003)
+        );
+    }
+
+    #[test]
+    fn forked_rejoin() {
+        let grid = CharGrid::from_str(
+            "#########
+###...###
+#S..#..E#
+###...###
+#########",
+        )
+        .unwrap();
+        let graph = Graph::build(
+            &grid,
+            &[Location::new(2, 1), Location::new(2, 7)],
+            Costs::UNIFORM,
+        );
+
+        // going over or under the wall is the same, so both are kept, but the
+        // distance is still how far it is
+        assert_eq!(graph.node(0).edges.len(), 1);
+        let edge = graph.node(0).edges[0];
+        assert_eq!((edge.to, edge.distance, edge.cost), (1, 7, 7));
+        assert_eq!(graph.routes(&edge).len(), 2);
+        assert_eq!(graph.route_tiles(&edge), 10);
+    }
+}
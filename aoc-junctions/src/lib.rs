@@ -0,0 +1,8 @@
+//! Collapse a wall (`#`) and floor maze down to a graph of its junctions,
+//! where each edge is a whole corridor, then search that graph instead of
+//! every tile.
+pub mod graph;
+pub mod search;
+
+pub use graph::{Costs, Edge, Graph, GraphNode};
+pub use search::{Distances, ShortestPaths};
@@ -0,0 +1,353 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use aoc_std::{directions::Cardinal, geometry::Location};
+use rustc_hash::FxHashSet;
+
+use crate::graph::{facing_idx, Edge, Graph, FACINGS};
+
+/// The lowest cost to reach each node facing each way.
+#[derive(Debug, Clone)]
+pub struct Distances {
+    dists: Vec<[usize; 4]>,
+}
+
+impl Distances {
+    /// The lowest cost to reach `node` facing `facing`, if we can.
+    pub fn get(&self, node: usize, facing: Cardinal) -> Option<usize> {
+        Some(self.dists[node][facing_idx(facing)]).filter(|d| *d != usize::MAX)
+    }
+
+    /// The lowest cost to reach `node` facing any way at all.
+    pub fn to(&self, node: usize) -> Option<usize> {
+        self.dists[node]
+            .iter()
+            .min()
+            .copied()
+            .filter(|d| *d != usize::MAX)
+    }
+}
+
+/// Every cheapest path between two nodes.
+#[derive(Debug, Clone)]
+pub struct ShortestPaths<'a> {
+    graph: &'a Graph,
+    from: usize,
+    to: usize,
+    facing: Cardinal,
+    cost: usize,
+    /// For each node and facing, the edges that carry on along a cheapest
+    /// path.
+    moves: Vec<[Vec<usize>; 4]>,
+}
+
+impl ShortestPaths<'_> {
+    pub fn cost(&self) -> usize {
+        self.cost
+    }
+
+    /// Every tile on at least one of the paths, expanded back out from the
+    /// edges between the junctions.
+    pub fn tiles(&self) -> FxHashSet<Location> {
+        let mut tiles = FxHashSet::default();
+        tiles.insert(self.graph.node(self.from).location);
+
+        for (node, moves) in self.moves.iter().enumerate() {
+            for &j in moves.iter().flatten() {
+                let edge = &self.graph.node(node).edges[j];
+                tiles.insert(self.graph.node(edge.to).location);
+                tiles.extend(self.graph.routes(edge).iter().flatten());
+            }
+        }
+
+        tiles
+    }
+
+    /// Every path, tile by tile. There can be a lot of these in an open maze.
+    pub fn paths(&self) -> Vec<Vec<Location>> {
+        let mut paths = Vec::new();
+        let start = vec![self.graph.node(self.from).location];
+        self.extend_paths(self.from, self.facing, start, &mut paths);
+        paths
+    }
+
+    fn extend_paths(
+        &self,
+        node: usize,
+        facing: Cardinal,
+        path: Vec<Location>,
+        paths: &mut Vec<Vec<Location>>,
+    ) {
+        for &j in self.moves[node][facing_idx(facing)].iter() {
+            let edge = &self.graph.node(node).edges[j];
+            for route in self.graph.routes(edge).iter() {
+                let mut next = path.clone();
+                next.extend_from_slice(route);
+                next.push(self.graph.node(edge.to).location);
+
+                if edge.to == self.to {
+                    paths.push(next);
+                } else {
+                    self.extend_paths(edge.to, edge.exit_dir, next, paths);
+                }
+            }
+        }
+    }
+}
+
+impl Graph {
+    /// The lowest cost to reach every node facing every way, starting at
+    /// `from` facing `facing`. With a `to`, this stops once every cost no
+    /// higher than reaching `to` is known, and anything more expensive is
+    /// only an upper bound.
+    pub fn dijkstra(&self, from: usize, facing: Cardinal, to: Option<usize>) -> Distances {
+        let mut dists = vec![[usize::MAX; 4]; self.nodes().len()];
+        let mut heap = BinaryHeap::default();
+
+        dists[from][facing_idx(facing)] = 0;
+        heap.push(State {
+            node: from,
+            facing,
+            cost: 0,
+        });
+
+        let mut min = usize::MAX;
+
+        while let Some(State { node, facing, cost }) = heap.pop() {
+            if cost > min {
+                break;
+            }
+
+            if dists[node][facing_idx(facing)] < cost {
+                continue;
+            }
+
+            if Some(node) == to {
+                min = cost;
+                continue;
+            }
+
+            for edge in self.node(node).edges.iter() {
+                let Some(leave) = self.leave_cost(from, node, facing, edge) else {
+                    continue;
+                };
+
+                let next_cost = cost + leave;
+                let next_node = edge.to;
+
+                let seen = &mut dists[next_node][facing_idx(edge.exit_dir)];
+                if *seen <= next_cost {
+                    continue;
+                }
+                *seen = next_cost;
+
+                heap.push(State {
+                    node: next_node,
+                    cost: next_cost,
+                    facing: edge.exit_dir,
+                });
+            }
+        }
+
+        Distances { dists }
+    }
+
+    /// Every cheapest path from `from`, starting out facing `facing`, to `to`
+    /// facing any way at all, or `None` if we can't get there. `from` and `to`
+    /// have to be different, and steps have to cost something, or there could
+    /// be infinitely many paths.
+    pub fn shortest_paths(
+        &self,
+        from: usize,
+        facing: Cardinal,
+        to: usize,
+    ) -> Option<ShortestPaths<'_>> {
+        if self.costs().step == 0 || from == to {
+            return None;
+        }
+
+        let dists = self.dijkstra(from, facing, Some(to)).dists;
+        let min = dists[to]
+            .iter()
+            .min()
+            .copied()
+            .filter(|d| *d != usize::MAX)?;
+
+        let mut moves: Vec<[Vec<usize>; 4]> = vec![Default::default(); self.nodes().len()];
+        let mut on_best = vec![[false; 4]; self.nodes().len()];
+
+        // steps always cost something, so working back from the most expensive
+        // state means we've always decided everything that comes after it
+        let mut settled: Vec<(usize, usize)> = (0..self.nodes().len())
+            .flat_map(|n| (0..4).map(move |f| (n, f)))
+            .filter(|(n, f)| dists[*n][*f] <= min)
+            .collect();
+        settled.sort_unstable_by_key(|(n, f)| std::cmp::Reverse(dists[*n][*f]));
+
+        for (node, f) in settled {
+            if node == to {
+                on_best[node][f] = dists[node][f] == min;
+                continue;
+            }
+
+            for (j, edge) in self.node(node).edges.iter().enumerate() {
+                let Some(leave) = self.leave_cost(from, node, FACINGS[f], edge) else {
+                    continue;
+                };
+
+                let next = facing_idx(edge.exit_dir);
+                if dists[node][f] + leave == dists[edge.to][next] && on_best[edge.to][next] {
+                    moves[node][f].push(j);
+                }
+            }
+
+            on_best[node][f] = !moves[node][f].is_empty();
+        }
+
+        Some(ShortestPaths {
+            graph: self,
+            from,
+            to,
+            facing,
+            cost: min,
+            moves,
+        })
+    }
+
+    /// The cost of leaving `node` along `edge` when facing `facing`, or `None`
+    /// if that means turning around. Turning around is only ever worth it
+    /// where we started, anywhere else it just takes us back where we came
+    /// from.
+    fn leave_cost(&self, from: usize, node: usize, facing: Cardinal, edge: &Edge) -> Option<usize> {
+        if node != from && edge.enter_dir.opposite() == facing {
+            return None;
+        }
+
+        Some(self.costs().move_cost(facing, edge.enter_dir) + edge.cost)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+struct State {
+    node: usize,
+    facing: Cardinal,
+    cost: usize,
+}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .cmp(&self.cost)
+            .then_with(|| self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use aoc_std::collections::CharGrid;
+
+    use crate::Costs;
+
+    use super::*;
+
+    const LOOP: &str = "#####
+#S..#
+#.#.#
+#..E#
+#####";
+
+    fn build(input: &str, costs: Costs) -> Graph {
+        let grid = CharGrid::from_str(input).unwrap();
+        let mut keep = Vec::new();
+        for ch in ['S', 'E'] {
+            for (r, row) in grid.locations.iter().enumerate() {
+                if let Some(c) = row.iter().position(|v| *v == ch) {
+                    keep.push(Location::new(r, c));
+                }
+            }
+        }
+        Graph::build(&grid, &keep, costs)
+    }
+
+    #[test]
+    fn uniform() {
+        let graph = build(
+            "#######
+#S....#
+#.###.#
+#.....#
+###.###
+#E..#.#
+#######",
+            Costs::UNIFORM,
+        );
+
+        let dists = graph.dijkstra(0, Cardinal::East, None);
+        assert_eq!(dists.to(1), Some(8));
+        assert_eq!(dists.get(1, Cardinal::West), Some(8));
+        assert_eq!(dists.get(1, Cardinal::North), None);
+
+        let paths = graph.shortest_paths(0, Cardinal::East, 1).unwrap();
+        assert_eq!(paths.cost(), 8);
+        assert_eq!(paths.tiles().len(), 9);
+        assert_eq!(
+            paths.paths(),
+            vec![vec![
+                Location::new(1, 1),
+                Location::new(2, 1),
+                Location::new(3, 1),
+                Location::new(3, 2),
+                Location::new(3, 3),
+                Location::new(4, 3),
+                Location::new(5, 3),
+                Location::new(5, 2),
+                Location::new(5, 1),
+            ]]
+        );
+    }
+
+    #[test]
+    fn all_shortest_paths() {
+        let graph = build(LOOP, Costs::UNIFORM);
+        let paths = graph.shortest_paths(0, Cardinal::East, 1).unwrap();
+        assert_eq!(paths.cost(), 4);
+        assert_eq!(paths.paths().len(), 2);
+        assert_eq!(paths.tiles().len(), 8);
+
+        // expensive turns make going the way we're facing the only option
+        let graph = build(
+            LOOP,
+            Costs {
+                step: 1,
+                turn: 1000,
+            },
+        );
+        let paths = graph.shortest_paths(0, Cardinal::East, 1).unwrap();
+        assert_eq!(paths.cost(), 1004);
+        assert_eq!(paths.paths().len(), 1);
+        assert_eq!(paths.paths()[0][1], Location::new(1, 2));
+
+        // unless we started out facing the other way
+        let paths = graph.shortest_paths(0, Cardinal::South, 1).unwrap();
+        assert_eq!(paths.paths()[0][1], Location::new(2, 1));
+    }
+
+    #[test]
+    fn no_paths() {
+        let graph = build("#####\n#S#E#\n#####", Costs::UNIFORM);
+        assert!(graph.shortest_paths(0, Cardinal::East, 1).is_none());
+        assert_eq!(graph.dijkstra(0, Cardinal::East, None).to(1), None);
+
+        let graph = build(LOOP, Costs { step: 0, turn: 1 });
+        assert!(graph.shortest_paths(0, Cardinal::East, 1).is_none());
+    }
+}
@@ -1,52 +1,239 @@
-use std::str::FromStr;
+use std::{fmt::Debug, hash::Hash, str::FromStr};
 
 use anyhow::anyhow;
 use aoc_plumbing::Problem;
 use nom::{character::complete, combinator, multi::separated_list1, IResult};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// The ways in which a list of pages can fail to have a single correct order.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OrderingError<T: Debug> {
+    #[error("page {0:?} appears more than once")]
+    DuplicatePage(T),
+    #[error("rules contain a cycle: {0:?}")]
+    Cycle(Vec<T>),
+    #[error("ordering is ambiguous between pages: {0:?}")]
+    Ambiguous(Vec<T>),
+}
+
+/// A general set of `before|after` ordering rules over arbitrary values.
+///
+/// Unlike the bitset representation used for the puzzle itself, this places
+/// no limit on the values involved, so it's usable for things like ordering
+/// build steps by their dependencies.
+#[derive(Debug, Clone)]
+pub struct OrderingRules<T> {
+    after: FxHashMap<T, FxHashSet<T>>,
+    before: FxHashMap<T, FxHashSet<T>>,
+}
+
+impl<T> Default for OrderingRules<T> {
+    fn default() -> Self {
+        Self {
+            after: FxHashMap::default(),
+            before: FxHashMap::default(),
+        }
+    }
+}
+
+impl<T> OrderingRules<T>
+where
+    T: Copy + Eq + Hash + Debug,
+{
+    /// Record that `before` must appear somewhere before `after`.
+    pub fn add_rule(&mut self, before: T, after: T) {
+        self.after.entry(before).or_default().insert(after);
+        self.before.entry(after).or_default().insert(before);
+    }
+
+    /// True if no pair of values in `pages` violates a rule.
+    pub fn is_valid(&self, pages: &[T]) -> bool {
+        let mut seen = FxHashSet::default();
+        for page in pages.iter() {
+            if let Some(after) = self.after.get(page) {
+                if after.iter().any(|p| seen.contains(p)) {
+                    return false;
+                }
+            }
+
+            seen.insert(*page);
+        }
+
+        true
+    }
+
+    /// Produce the single order of `pages` that satisfies every rule that
+    /// applies to them.
+    ///
+    /// Rules that mention values not present in `pages` are ignored. If the
+    /// applicable rules contain a cycle, or do not constrain the pages enough
+    /// to produce exactly one order, an error is returned.
+    pub fn reorder(&self, pages: &[T]) -> Result<Vec<T>, OrderingError<T>> {
+        let mut in_degree: FxHashMap<T, usize> = FxHashMap::default();
+        for page in pages.iter().copied() {
+            if in_degree.insert(page, 0).is_some() {
+                return Err(OrderingError::DuplicatePage(page));
+            }
+        }
+
+        for page in pages.iter() {
+            for next in self.successors(page) {
+                if let Some(deg) = in_degree.get_mut(next) {
+                    *deg += 1;
+                }
+            }
+        }
+
+        let mut ready: Vec<T> = pages
+            .iter()
+            .filter(|p| in_degree[*p] == 0)
+            .copied()
+            .collect();
+        let mut out = Vec::with_capacity(pages.len());
+
+        while let Some(page) = ready.pop() {
+            // more than one page with nothing left in front of it means the
+            // rules don't decide which comes first
+            if !ready.is_empty() {
+                ready.push(page);
+                return Err(OrderingError::Ambiguous(ready));
+            }
+
+            out.push(page);
+
+            for next in self.successors(&page) {
+                if let Some(deg) = in_degree.get_mut(next) {
+                    *deg -= 1;
+                    if *deg == 0 {
+                        ready.push(*next);
+                    }
+                }
+            }
+        }
+
+        if out.len() != pages.len() {
+            return Err(OrderingError::Cycle(self.find_cycle(&in_degree)));
+        }
+
+        Ok(out)
+    }
+
+    fn successors<'a>(&'a self, page: &T) -> impl Iterator<Item = &'a T> {
+        self.after.get(page).into_iter().flatten()
+    }
+
+    // Every page left with a non-zero in-degree after the sort has at least
+    // one remaining predecessor, so walking backwards must eventually revisit
+    // a page, and that loop is our cycle.
+    fn find_cycle(&self, in_degree: &FxHashMap<T, usize>) -> Vec<T> {
+        let remaining = |p: &T| in_degree.get(p).is_some_and(|d| *d > 0);
+
+        let Some(start) = in_degree.keys().find(|p| remaining(p)) else {
+            return Vec::new();
+        };
+
+        let mut path = vec![*start];
+        let mut cur = *start;
+        loop {
+            // the invariant above guarantees this exists
+            let prev = self.before[&cur]
+                .iter()
+                .find(|p| remaining(p))
+                .copied()
+                .expect("remaining page without a remaining predecessor");
+
+            if let Some(idx) = path.iter().position(|p| *p == prev) {
+                let mut cycle = path.split_off(idx);
+                cycle.reverse();
+                return cycle;
+            }
+
+            path.push(prev);
+            cur = prev;
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct PrintQueue {
     rules_left: [u128; 100],
     rules_right: [u128; 100],
+    rules: OrderingRules<u64>,
     updates: Vec<Update>,
+    // if every page fits in the bitsets, we can use the fast path
+    small: bool,
+}
+
+impl PrintQueue {
+    /// The general form of the ordering rules.
+    pub fn rules(&self) -> &OrderingRules<u64> {
+        &self.rules
+    }
+
+    /// The fully corrected page order for each update that was not already
+    /// in the correct order.
+    pub fn corrected_updates(
+        &self,
+    ) -> impl Iterator<Item = Result<Vec<u64>, OrderingError<u64>>> + '_ {
+        self.updates
+            .iter()
+            .filter(|u| !self.rules.is_valid(&u.pages))
+            .map(|u| self.rules.reorder(&u.pages))
+    }
 }
 
 impl FromStr for PrintQueue {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (rules, update_str) = s
+        let (rules_raw, update_str) = s
             .trim()
             .split_once("\n\n")
             .ok_or_else(|| anyhow!("invalid input"))?;
 
-        let mut rules_left = [0; 100];
-        let mut rules_right = [0; 100];
+        let mut rules = OrderingRules::default();
+        let mut small = true;
 
-        for raw_rule in rules.lines() {
+        for raw_rule in rules_raw.lines() {
             let (left_raw, right_raw) = raw_rule
                 .split_once('|')
                 .ok_or_else(|| anyhow!("invalid input"))?;
-            let left: u8 = left_raw.parse()?;
-            let right: u8 = right_raw.parse()?;
+            let left: u64 = left_raw.parse()?;
+            let right: u64 = right_raw.parse()?;
 
-            rules_left[left as usize] |= 1 << right;
-            rules_right[right as usize] |= 1 << left;
+            small &= left < 100 && right < 100;
+            rules.add_rule(left, right);
         }
 
         let (_, updates) = parse_updates(update_str).map_err(|e| e.to_owned())?;
 
+        small &= updates.iter().all(|u| u.pages.iter().all(|p| *p < 100));
+
+        let mut rules_left = [0; 100];
+        let mut rules_right = [0; 100];
+
+        if small {
+            for (left, rights) in rules.after.iter() {
+                for right in rights.iter() {
+                    rules_left[*left as usize] |= 1 << right;
+                    rules_right[*right as usize] |= 1 << left;
+                }
+            }
+        }
+
         Ok(Self {
             rules_left,
             rules_right,
+            rules,
             updates,
+            small,
         })
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 struct Update {
-    pages: Vec<u8>,
+    pages: Vec<u64>,
 }
 
 impl Update {
@@ -68,13 +255,13 @@ impl Update {
         true
     }
 
-    pub fn middle(&self) -> u8 {
+    pub fn middle(&self) -> u64 {
         self.pages[self.pages.len() / 2]
     }
 
     // we don't need to actually re-order the list, we just need to know what
     // the middle number _would_ be
-    pub fn middle_reorder(&self, rules_left: &[u128], rules_right: &[u128]) -> u8 {
+    pub fn middle_reorder(&self, rules_left: &[u128], rules_right: &[u128]) -> Option<u64> {
         let each_side = (self.pages.len() / 2) as u32;
 
         let mut seen = 0_u128;
@@ -97,12 +284,11 @@ impl Update {
                 continue;
             }
 
-            return page;
+            return Some(page);
         }
 
-        // for this problem to have unambiguous solutions, this must be true
-        // that we can never reach this line.
-        unreachable!()
+        // for unambiguous rules, we can never get here
+        None
     }
 }
 
@@ -112,7 +298,7 @@ fn parse_updates(input: &str) -> IResult<&str, Vec<Update>> {
 
 fn parse_update(input: &str) -> IResult<&str, Update> {
     combinator::map(
-        separated_list1(complete::char(','), complete::u8),
+        separated_list1(complete::char(','), complete::u64),
         |pages| Update { pages },
     )(input)
 }
@@ -127,21 +313,47 @@ impl Problem for PrintQueue {
     type P2 = u64;
 
     fn part_one(&mut self) -> Result<Self::P1, Self::ProblemError> {
+        if !self.small {
+            return Ok(self
+                .updates
+                .iter()
+                .filter(|&u| self.rules.is_valid(&u.pages))
+                .map(|u| u.middle())
+                .sum());
+        }
+
         Ok(self
             .updates
             .iter()
             .filter(|&u| u.is_valid(&self.rules_left))
-            .map(|u| u.middle() as u64)
+            .map(|u| u.middle())
             .sum())
     }
 
     fn part_two(&mut self) -> Result<Self::P2, Self::ProblemError> {
-        Ok(self
-            .updates
+        if !self.small {
+            return self.corrected_updates().try_fold(0, |acc, pages| {
+                let pages = pages?;
+                Ok(acc + pages[pages.len() / 2])
+            });
+        }
+
+        self.updates
             .iter()
             .filter(|&u| !u.is_valid(&self.rules_left))
-            .map(|u| u.middle_reorder(&self.rules_left, &self.rules_right) as u64)
-            .sum())
+            .try_fold(0, |acc, u| {
+                // the fast path can't tell us why it failed, so fall back to
+                // the general form to get a useful error
+                match u.middle_reorder(&self.rules_left, &self.rules_right) {
+                    Some(middle) => Ok(acc + middle),
+                    None => Err(self
+                        .rules
+                        .reorder(&u.pages)
+                        .err()
+                        .map(anyhow::Error::from)
+                        .unwrap_or_else(|| anyhow!("could not reorder {:?}", u.pages))),
+                }
+            })
     }
 }
 
@@ -159,9 +371,7 @@ mod tests {
         assert_eq!(solution, Solution::new(4996, 6311));
     }
 
-    #[test]
-    fn example() {
-        let input = "47|53
+    const EXAMPLE: &str = "47|53
 97|13
 97|61
 97|47
@@ -189,7 +399,58 @@ mod tests {
 75,97,47,61,53
 61,13,29
 97,13,75,29,47";
-        let solution = PrintQueue::solve(input).unwrap();
+
+    #[test]
+    fn example() {
+        let solution = PrintQueue::solve(EXAMPLE).unwrap();
         assert_eq!(solution, Solution::new(143, 123));
     }
+
+    #[test]
+    fn corrected_updates() {
+        let inst = PrintQueue::from_str(EXAMPLE).unwrap();
+        let corrected = inst
+            .corrected_updates()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            corrected,
+            vec![
+                vec![97, 75, 47, 61, 53],
+                vec![61, 29, 13],
+                vec![97, 75, 47, 29, 13],
+            ]
+        );
+    }
+
+    #[test]
+    fn ordering_errors() {
+        let mut rules = OrderingRules::default();
+        rules.add_rule(1000, 2000);
+        rules.add_rule(2000, 3000);
+        assert_eq!(
+            rules.reorder(&[3000, 1000, 2000]),
+            Ok(vec![1000, 2000, 3000])
+        );
+        assert_eq!(
+            rules.reorder(&[1000, 1000]),
+            Err(OrderingError::DuplicatePage(1000))
+        );
+        assert!(matches!(
+            rules.reorder(&[1000, 2000, 4000]),
+            Err(OrderingError::Ambiguous(_))
+        ));
+
+        rules.add_rule(3000, 1000);
+        assert_eq!(
+            rules.reorder(&[3000, 1000, 2000]).map_err(|e| match e {
+                OrderingError::Cycle(mut c) => {
+                    c.sort();
+                    c
+                }
+                _ => vec![],
+            }),
+            Err(vec![1000, 2000, 3000])
+        );
+    }
 }
@@ -18,6 +18,23 @@ pub struct GuardGallivant {
     candidate_states: FxHashMap<Guard, Location>,
 }
 
+/// An obstruction that would trap the guard in a loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopObstruction {
+    pub location: Location,
+    /// The repeating portion of the guard's route with the obstruction in
+    /// place, starting from the first repeated state.
+    pub cycle: Vec<(Location, Cardinal)>,
+}
+
+/// The step-by-step states of a guard's route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Route {
+    states: Vec<(Location, Cardinal)>,
+    /// If the route loops, the index of the state the loop returns to.
+    loop_start: Option<usize>,
+}
+
 impl FromStr for GuardGallivant {
     type Err = anyhow::Error;
 
@@ -27,8 +44,8 @@ impl FromStr for GuardGallivant {
             location: Location::default(),
             facing: Cardinal::North,
         };
-        let mut obstacles_rows = vec![WideMap::default(); grid.height()];
-        let mut obstacles_cols = vec![WideMap::default(); grid.width()];
+        let mut obstacles_rows = vec![WideMap::new(grid.width()); grid.height()];
+        let mut obstacles_cols = vec![WideMap::new(grid.height()); grid.width()];
 
         #[allow(clippy::needless_range_loop)]
         for r in 0..grid.height() {
@@ -55,6 +72,151 @@ impl FromStr for GuardGallivant {
 }
 
 impl GuardGallivant {
+    /// The guard's route until they leave the map, as an ordered list of
+    /// locations and facings. Turning in place produces a new state at the
+    /// same location.
+    ///
+    /// If the map traps the guard in a loop, the route stops before the first
+    /// repeated state.
+    pub fn path(&self) -> Vec<(Location, Cardinal)> {
+        self.trace(None).states
+    }
+
+    /// Every location where placing a single obstruction would trap the
+    /// guard in a loop, sorted by location.
+    pub fn loop_obstructions(&self) -> Vec<LoopObstruction> {
+        let (_, candidates) = self.walk();
+
+        let mut obstructions: Vec<_> = candidates
+            .par_iter()
+            .filter(|(state, obstruction)| self.valid_configuration(**state, **obstruction))
+            .map(|(_, obstruction)| {
+                let route = self.trace(Some(*obstruction));
+                // valid_configuration already told us this loops
+                let start = route.loop_start.unwrap_or_default();
+                LoopObstruction {
+                    location: *obstruction,
+                    cycle: route.states[start..].to_vec(),
+                }
+            })
+            .collect();
+
+        obstructions.sort_unstable_by_key(|o| o.location);
+
+        obstructions
+    }
+
+    /// Render the map with the guard's route and every loop-producing
+    /// obstruction marked, in the style of the puzzle text.
+    pub fn render(&self) -> String {
+        let obstructions: Vec<_> = self
+            .loop_obstructions()
+            .into_iter()
+            .map(|o| o.location)
+            .collect();
+        self.render_route(&self.path(), &obstructions)
+    }
+
+    /// Render the map with just the given loop and its obstruction marked.
+    pub fn render_loop(&self, obstruction: &LoopObstruction) -> String {
+        self.render_route(&obstruction.cycle, &[obstruction.location])
+    }
+
+    fn render_route(&self, states: &[(Location, Cardinal)], obstructions: &[Location]) -> String {
+        const VERTICAL: u8 = 1;
+        const HORIZONTAL: u8 = 2;
+
+        let mut marks = vec![vec![0_u8; self.grid.width()]; self.grid.height()];
+        for (loc, facing) in states.iter() {
+            marks[loc.row][loc.col] |= match facing {
+                Cardinal::North | Cardinal::South => VERTICAL,
+                Cardinal::East | Cardinal::West => HORIZONTAL,
+            };
+        }
+
+        let mut out = String::with_capacity((self.grid.width() + 1) * self.grid.height());
+        for (r, row) in self.grid.locations.iter().enumerate() {
+            for (c, ch) in row.iter().enumerate() {
+                let loc = Location::new(r, c);
+                if obstructions.contains(&loc) {
+                    out.push('O');
+                } else if loc == self.guard.location {
+                    out.push('^');
+                } else {
+                    out.push(match marks[r][c] {
+                        VERTICAL => '|',
+                        HORIZONTAL => '-',
+                        0 => *ch,
+                        _ => '+',
+                    });
+                }
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+
+    /// Walk the guard off the map, collecting the locations they visit and
+    /// the states immediately prior to visiting each location for the first
+    /// time.
+    fn walk(&self) -> (FxHashSet<Location>, FxHashMap<Guard, Location>) {
+        let mut seen_locations = FxHashSet::default();
+        let mut candidate_states = FxHashMap::default();
+        seen_locations.insert(self.guard.location);
+
+        let mut guard = self.guard;
+        while let Some((loc, ch)) = self.grid.cardinal_neighbor(&guard.location, guard.facing) {
+            match ch {
+                '#' => guard.facing = guard.facing.right(),
+                _ => {
+                    // if we haven't already seen the location we're going to
+                    // we can add our _current_ configuration to the list of
+                    // candidate states
+                    if !seen_locations.contains(&loc) {
+                        candidate_states.insert(guard, loc);
+                        seen_locations.insert(loc);
+                    }
+                    guard.location = loc;
+                }
+            }
+        }
+
+        (seen_locations, candidate_states)
+    }
+
+    /// Step the guard one tile at a time, with an optional extra obstruction,
+    /// until they leave the map or repeat a state.
+    fn trace(&self, obstruction: Option<Location>) -> Route {
+        let mut guard = self.guard;
+        let mut states = vec![(guard.location, guard.facing)];
+        let mut seen = FxHashMap::default();
+        seen.insert(guard, 0);
+
+        while let Some((loc, ch)) = self.grid.cardinal_neighbor(&guard.location, guard.facing) {
+            if matches!(ch, '#') || obstruction == Some(loc) {
+                guard.facing = guard.facing.right();
+            } else {
+                guard.location = loc;
+            }
+
+            if let Some(idx) = seen.get(&guard) {
+                return Route {
+                    states,
+                    loop_start: Some(*idx),
+                };
+            }
+
+            seen.insert(guard, states.len());
+            states.push((guard.location, guard.facing));
+        }
+
+        Route {
+            states,
+            loop_start: None,
+        }
+    }
+
     /// check if the specified configuration produces a loop
     fn valid_configuration(&self, mut guard: Guard, obstruction: Location) -> bool {
         let mut seen = FxHashSet::with_capacity_and_hasher(100, FxBuildHasher);
@@ -64,48 +226,40 @@ impl GuardGallivant {
             // ahead of us
             match guard.facing {
                 Cardinal::North => {
-                    let mut row_map = self.obstacles_rows[guard.location.row];
-                    if obstruction.row == guard.location.row {
-                        row_map.insert(obstruction.col);
-                    }
-
-                    if let Some(next_col) = row_map.next_right(guard.location.col) {
+                    let extra = (obstruction.row == guard.location.row).then_some(obstruction.col);
+                    if let Some(next_col) = self.obstacles_rows[guard.location.row]
+                        .next_right_with(guard.location.col, extra)
+                    {
                         guard.location.col = next_col;
                     } else {
                         return false;
                     }
                 }
                 Cardinal::South => {
-                    let mut row_map = self.obstacles_rows[guard.location.row];
-                    if obstruction.row == guard.location.row {
-                        row_map.insert(obstruction.col);
-                    }
-
-                    if let Some(next_col) = row_map.next_left(guard.location.col) {
+                    let extra = (obstruction.row == guard.location.row).then_some(obstruction.col);
+                    if let Some(next_col) = self.obstacles_rows[guard.location.row]
+                        .next_left_with(guard.location.col, extra)
+                    {
                         guard.location.col = next_col;
                     } else {
                         return false;
                     }
                 }
                 Cardinal::East => {
-                    let mut col_map = self.obstacles_cols[guard.location.col];
-                    if obstruction.col == guard.location.col {
-                        col_map.insert(obstruction.row);
-                    }
-
-                    if let Some(next_row) = col_map.next_right(guard.location.row) {
+                    let extra = (obstruction.col == guard.location.col).then_some(obstruction.row);
+                    if let Some(next_row) = self.obstacles_cols[guard.location.col]
+                        .next_right_with(guard.location.row, extra)
+                    {
                         guard.location.row = next_row;
                     } else {
                         return false;
                     }
                 }
                 Cardinal::West => {
-                    let mut col_map = self.obstacles_cols[guard.location.col];
-                    if obstruction.col == guard.location.col {
-                        col_map.insert(obstruction.row);
-                    }
-
-                    if let Some(next_row) = col_map.next_left(guard.location.row) {
+                    let extra = (obstruction.col == guard.location.col).then_some(obstruction.row);
+                    if let Some(next_row) = self.obstacles_cols[guard.location.col]
+                        .next_left_with(guard.location.row, extra)
+                    {
                         guard.location.row = next_row;
                     } else {
                         return false;
@@ -132,26 +286,9 @@ impl Problem for GuardGallivant {
     type P2 = usize;
 
     fn part_one(&mut self) -> Result<Self::P1, Self::ProblemError> {
-        let mut seen_locations = FxHashSet::default();
-        seen_locations.insert(self.guard.location);
-
-        let mut guard = self.guard;
-        while let Some((loc, ch)) = self.grid.cardinal_neighbor(&guard.location, guard.facing) {
-            match ch {
-                '#' => guard.facing = guard.facing.right(),
-                _ => {
-                    // if we haven't already seen the location we're going to
-                    // we can add our _current_ configuration to the list of
-                    // candidate states
-                    if !seen_locations.contains(&loc) {
-                        // we need this for part 2
-                        self.candidate_states.insert(guard, loc);
-                        seen_locations.insert(loc);
-                    }
-                    guard.location = loc;
-                }
-            }
-        }
+        let (seen_locations, candidate_states) = self.walk();
+        // we need this for part 2
+        self.candidate_states = candidate_states;
 
         Ok(seen_locations.len())
     }
@@ -175,44 +312,49 @@ struct Guard {
     facing: Cardinal,
 }
 
-/// Sigh, the whole 130x130 grid is a PITA. It might be better to make this
-/// align better, but I can't be bothered to do the math with more than two
-/// values.
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A row or column of obstacles, sized to fit the grid, that lets us jump
+/// straight to the next obstacle in either direction.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct WideMap {
-    left: u128,
-    right: u8,
+    words: Vec<u64>,
 }
 
 impl WideMap {
+    pub fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
     pub fn insert(&mut self, idx: usize) {
-        if idx < 128 {
-            self.left |= 1 << idx;
-        } else {
-            self.right |= 1 << (idx - 128);
+        let word = idx / 64;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
         }
+        self.words[word] |= 1 << (idx % 64);
     }
 
     /// get the next open space prior to an obstacle to our right, if one exists
     pub fn next_right(&self, idx: usize) -> Option<usize> {
-        if idx < 128 {
-            if idx < 127 {
-                let shifted = self.left >> (idx + 1);
-                if shifted > 0 {
-                    let offset = shifted.trailing_zeros() as usize;
-                    return Some(idx + offset);
-                }
-            }
-            if self.right > 0 {
-                let right_offset = self.right.trailing_zeros() as usize;
-                return Some(127 + right_offset);
-            }
-        } else {
-            let shifted = self.right >> (idx - 128 + 1);
-            if shifted > 0 {
-                let offset = shifted.trailing_zeros() as usize;
-                return Some(idx + offset);
+        let start = idx + 1;
+        let mut word = start / 64;
+        if word >= self.words.len() {
+            return None;
+        }
+
+        // the first word needs the bits at or below idx masked off
+        let mut bits = self.words[word] >> (start % 64);
+        if bits > 0 {
+            return Some(idx + bits.trailing_zeros() as usize);
+        }
+
+        word += 1;
+        while word < self.words.len() {
+            bits = self.words[word];
+            if bits > 0 {
+                return Some(word * 64 + bits.trailing_zeros() as usize - 1);
             }
+            word += 1;
         }
 
         None
@@ -220,31 +362,47 @@ impl WideMap {
 
     /// get the next open space prior to an obstacle to our left, if one exists
     pub fn next_left(&self, idx: usize) -> Option<usize> {
-        if idx < 128 {
-            if idx == 0 {
-                return None;
-            }
-            let shifted = self.left << (128 - idx);
-            if shifted > 0 {
-                let offset = shifted.leading_zeros() as usize;
-                return Some(idx - offset);
-            }
-        } else {
-            if idx > 128 {
-                let shifted = self.right << (8 - (idx - 128));
-                if shifted > 0 {
-                    let offset = shifted.leading_zeros() as usize;
-                    return Some(idx - offset);
-                }
-            }
-            if self.left > 0 {
-                let left_offset = self.left.leading_zeros() as usize;
-                return Some(128 - left_offset);
+        if idx == 0 || self.words.is_empty() {
+            return None;
+        }
+
+        let end = (idx - 1).min(self.words.len() * 64 - 1);
+        let mut word = end / 64;
+
+        // the first word needs the bits at or above idx masked off
+        let mut bits = self.words[word] << (63 - end % 64);
+        if bits > 0 {
+            return Some(end + 1 - bits.leading_zeros() as usize);
+        }
+
+        while word > 0 {
+            word -= 1;
+            bits = self.words[word];
+            if bits > 0 {
+                return Some(word * 64 + 64 - bits.leading_zeros() as usize);
             }
         }
 
         None
     }
+
+    /// like `next_right`, but with `extra` also treated as an obstacle
+    pub fn next_right_with(&self, idx: usize, extra: Option<usize>) -> Option<usize> {
+        let next = self.next_right(idx);
+        match extra {
+            Some(e) if e > idx => Some(next.map_or(e - 1, |n| n.min(e - 1))),
+            _ => next,
+        }
+    }
+
+    /// like `next_left`, but with `extra` also treated as an obstacle
+    pub fn next_left_with(&self, idx: usize, extra: Option<usize>) -> Option<usize> {
+        let next = self.next_left(idx);
+        match extra {
+            Some(e) if e < idx => Some(next.map_or(e + 1, |n| n.max(e + 1))),
+            _ => next,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -276,4 +434,100 @@ mod tests {
         let solution = GuardGallivant::solve(input).unwrap();
         assert_eq!(solution, Solution::new(41, 6));
     }
+
+    #[test]
+    fn render() {
+        let input = "....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#..^.....
+........#.
+#.........
+......#...";
+        let inst = GuardGallivant::from_str(input).unwrap();
+
+        let path = inst.path();
+        assert_eq!(path.first(), Some(&(Location::new(6, 4), Cardinal::North)));
+        assert_eq!(path.last(), Some(&(Location::new(9, 7), Cardinal::South)));
+
+        let obstructions = inst.loop_obstructions();
+        assert_eq!(
+            obstructions.iter().map(|o| o.location).collect::<Vec<_>>(),
+            vec![
+                Location::new(6, 3),
+                Location::new(7, 6),
+                Location::new(7, 7),
+                Location::new(8, 1),
+                Location::new(8, 3),
+                Location::new(9, 7),
+            ]
+        );
+
+        let expected = "....#.....
+....+---+#
+....|...|.
+..#.|...|.
+....|..#|.
+....|...|.
+.#.O^---+.
+........#.
+#.........
+......#...
+";
+        assert_eq!(inst.render_loop(&obstructions[0]), expected);
+    }
+
+    #[test]
+    fn wide_map() {
+        let mut map = WideMap::new(500);
+        map.insert(3);
+        map.insert(130);
+        map.insert(200);
+        map.insert(499);
+
+        assert_eq!(map.next_right(0), Some(2));
+        assert_eq!(map.next_right(3), Some(129));
+        assert_eq!(map.next_right(129), Some(129));
+        assert_eq!(map.next_right(131), Some(199));
+        assert_eq!(map.next_right(499), None);
+
+        assert_eq!(map.next_left(499), Some(201));
+        assert_eq!(map.next_left(200), Some(131));
+        assert_eq!(map.next_left(129), Some(4));
+        assert_eq!(map.next_left(3), None);
+        assert_eq!(map.next_left(0), None);
+    }
+
+    #[test]
+    fn large_map() {
+        // the example, tucked into the bottom right of a much larger map
+        let example = [
+            "....#.....",
+            ".........#",
+            "..........",
+            "..#.......",
+            ".......#..",
+            "..........",
+            ".#..^.....",
+            "........#.",
+            "#.........",
+            "......#...",
+        ];
+        let mut input = String::new();
+        for r in 0..500 {
+            if r >= 490 {
+                input.push_str(&".".repeat(490));
+                input.push_str(example[r - 490]);
+            } else {
+                input.push_str(&".".repeat(500));
+            }
+            input.push('\n');
+        }
+
+        let solution = GuardGallivant::solve(&input).unwrap();
+        assert_eq!(solution, Solution::new(41, 6));
+    }
 }
@@ -1,4 +1,9 @@
-use std::str::FromStr;
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+    ops::RangeInclusive,
+    str::FromStr,
+};
 
 use aoc_plumbing::Problem;
 use nom::{
@@ -13,10 +18,17 @@ use rayon::prelude::*;
 
 #[derive(Debug, Clone)]
 pub struct BridgeRepair {
+    equations: Vec<Equation>,
     p1: i64,
     p2: i64,
 }
 
+impl BridgeRepair {
+    pub fn equations(&self) -> &[Equation] {
+        &self.equations
+    }
+}
+
 impl FromStr for BridgeRepair {
     type Err = anyhow::Error;
 
@@ -24,11 +36,11 @@ impl FromStr for BridgeRepair {
         let (_, equations) = parse_equations(s).map_err(|e| e.to_owned())?;
 
         let (p1, p2) = equations
-            .into_par_iter()
+            .par_iter()
             .map(|eq| eq.is_valid_combined_unwind_dfs())
             .reduce(|| (0, 0), |(p1, p2), (a1, a2)| (p1 + a1, p2 + a2));
 
-        Ok(Self { p1, p2 })
+        Ok(Self { equations, p1, p2 })
     }
 }
 
@@ -57,7 +69,358 @@ pub struct Equation {
     widths: Vec<i64>,
 }
 
+/// The left-hand operands that could have produced a given result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unwound {
+    None,
+    One(i64),
+    Range(RangeInclusive<i64>),
+    /// Every left-hand operand produces the result.
+    Any,
+}
+
+/// A binary operator that can be placed between an equation's operands.
+///
+/// Solving works backwards from the target, so as well as evaluating the
+/// operator we need to be able to undo it.
+pub trait Operator: Debug + Display + Send + Sync {
+    /// Evaluate `left op right`, if the result is defined.
+    fn apply(&self, left: i64, right: i64) -> Option<i64>;
+
+    /// Given `result = left op right`, the possible values of `left`.
+    fn unwind(&self, result: i64, right: i64) -> Unwound;
+
+    /// True if this operator can never produce a negative value from
+    /// non-negative operands. If every operator in a set has this property,
+    /// negative intermediate values can be pruned from the search.
+    fn preserves_non_negative(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Add;
+
+impl Operator for Add {
+    fn apply(&self, left: i64, right: i64) -> Option<i64> {
+        left.checked_add(right)
+    }
+
+    fn unwind(&self, result: i64, right: i64) -> Unwound {
+        result
+            .checked_sub(right)
+            .map_or(Unwound::None, Unwound::One)
+    }
+
+    fn preserves_non_negative(&self) -> bool {
+        true
+    }
+}
+
+impl Display for Add {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "+")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Mul;
+
+impl Operator for Mul {
+    fn apply(&self, left: i64, right: i64) -> Option<i64> {
+        left.checked_mul(right)
+    }
+
+    fn unwind(&self, result: i64, right: i64) -> Unwound {
+        if right == 0 {
+            if result == 0 {
+                Unwound::Any
+            } else {
+                Unwound::None
+            }
+        } else if result % right == 0 {
+            Unwound::One(result / right)
+        } else {
+            Unwound::None
+        }
+    }
+
+    fn preserves_non_negative(&self) -> bool {
+        true
+    }
+}
+
+impl Display for Mul {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "*")
+    }
+}
+
+/// Concatenation of the digits of the operands, in an arbitrary base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Concat {
+    base: i64,
+}
+
+impl Default for Concat {
+    fn default() -> Self {
+        Self::DECIMAL
+    }
+}
+
+impl Concat {
+    pub const DECIMAL: Self = Self { base: 10 };
+
+    /// # Panics
+    /// If `base` is less than 2.
+    pub fn new(base: i64) -> Self {
+        assert!(base >= 2, "concatenation base must be at least 2");
+        Self { base }
+    }
+
+    /// The value `left` needs to be multiplied by to make room for the digits
+    /// of `right`.
+    fn width(&self, right: i64) -> Option<i64> {
+        let mut width = self.base;
+        let mut rem = right / self.base;
+        while rem > 0 {
+            width = width.checked_mul(self.base)?;
+            rem /= self.base;
+        }
+        Some(width)
+    }
+}
+
+impl Operator for Concat {
+    fn apply(&self, left: i64, right: i64) -> Option<i64> {
+        if left < 0 || right < 0 {
+            return None;
+        }
+        left.checked_mul(self.width(right)?)?.checked_add(right)
+    }
+
+    fn unwind(&self, result: i64, right: i64) -> Unwound {
+        if result < 0 || right < 0 {
+            return Unwound::None;
+        }
+
+        match self.width(right) {
+            Some(width) if result % width == right => Unwound::One(result / width),
+            _ => Unwound::None,
+        }
+    }
+
+    fn preserves_non_negative(&self) -> bool {
+        true
+    }
+}
+
+impl Display for Concat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.base == 10 {
+            write!(f, "||")
+        } else {
+            write!(f, "||{}", self.base)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Sub;
+
+impl Operator for Sub {
+    fn apply(&self, left: i64, right: i64) -> Option<i64> {
+        left.checked_sub(right)
+    }
+
+    fn unwind(&self, result: i64, right: i64) -> Unwound {
+        result
+            .checked_add(right)
+            .map_or(Unwound::None, Unwound::One)
+    }
+}
+
+impl Display for Sub {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "-")
+    }
+}
+
+/// Integer division, truncating towards zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Div;
+
+impl Operator for Div {
+    fn apply(&self, left: i64, right: i64) -> Option<i64> {
+        left.checked_div(right)
+    }
+
+    fn unwind(&self, result: i64, right: i64) -> Unwound {
+        if right == 0 {
+            return Unwound::None;
+        }
+
+        // x / -d == r is the same as x / d == -r
+        let (d, r) = if right < 0 {
+            (right.unsigned_abs() as i64, -result)
+        } else {
+            (right, result)
+        };
+
+        let range = (|| {
+            let base = r.checked_mul(d)?;
+            Some(match r.cmp(&0) {
+                Ordering::Greater => base..=base.checked_add(d - 1)?,
+                Ordering::Equal => -(d - 1)..=(d - 1),
+                Ordering::Less => base.checked_sub(d - 1)?..=base,
+            })
+        })();
+
+        range.map_or(Unwound::None, Unwound::Range)
+    }
+
+    fn preserves_non_negative(&self) -> bool {
+        true
+    }
+}
+
+impl Display for Div {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "/")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Xor;
+
+impl Operator for Xor {
+    fn apply(&self, left: i64, right: i64) -> Option<i64> {
+        Some(left ^ right)
+    }
+
+    fn unwind(&self, result: i64, right: i64) -> Unwound {
+        Unwound::One(result ^ right)
+    }
+
+    fn preserves_non_negative(&self) -> bool {
+        true
+    }
+}
+
+impl Display for Xor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "^")
+    }
+}
+
+/// The operators available in part one.
+pub const PART_ONE_OPERATORS: &[&dyn Operator] = &[&Mul, &Add];
+
+/// The operators available in part two.
+pub const PART_TWO_OPERATORS: &[&dyn Operator] = &[&Mul, &Add, &Concat::DECIMAL];
+
+/// The operators placed between an equation's operands, evaluated left to
+/// right.
+#[derive(Debug, Clone)]
+pub struct Expression<'a> {
+    pub operands: Vec<i64>,
+    pub ops: Vec<&'a dyn Operator>,
+}
+
+impl Display for Expression<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, v) in self.operands.iter().enumerate() {
+            if i > 0 {
+                write!(f, " {} ", self.ops[i - 1])?;
+            }
+            write!(f, "{}", v)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl Equation {
+    pub fn target(&self) -> i64 {
+        self.left
+    }
+
+    pub fn operands(&self) -> &[i64] {
+        &self.right
+    }
+
+    /// True if some combination of `operators` makes this equation true.
+    pub fn is_satisfiable(&self, operators: &[&dyn Operator]) -> bool {
+        let mut found = false;
+        self.for_each_solution(operators, |_| {
+            found = true;
+            false
+        });
+        found
+    }
+
+    /// The first combination of `operators` found that makes this equation
+    /// true, if one exists.
+    pub fn solve<'a>(&self, operators: &[&'a dyn Operator]) -> Option<Expression<'a>> {
+        let mut found = None;
+        self.for_each_solution(operators, |ops| {
+            found = Some(self.expression(ops));
+            false
+        });
+        found
+    }
+
+    /// Every combination of `operators` that makes this equation true.
+    pub fn solutions<'a>(&self, operators: &[&'a dyn Operator]) -> Vec<Expression<'a>> {
+        let mut found = Vec::new();
+        self.for_each_solution(operators, |ops| {
+            found.push(self.expression(ops));
+            true
+        });
+        found
+    }
+
+    /// The number of combinations of `operators` that make this equation
+    /// true.
+    pub fn num_solutions(&self, operators: &[&dyn Operator]) -> usize {
+        let mut count = 0;
+        self.for_each_solution(operators, |_| {
+            count += 1;
+            true
+        });
+        count
+    }
+
+    fn expression<'a>(&self, ops: &[&'a dyn Operator]) -> Expression<'a> {
+        Expression {
+            operands: self.right.clone(),
+            ops: ops.to_vec(),
+        }
+    }
+
+    /// Calls `f` with each solution's operators, in order, until `f` returns
+    /// false.
+    fn for_each_solution<'a, F>(&self, operators: &[&'a dyn Operator], mut f: F)
+    where
+        F: FnMut(&[&'a dyn Operator]) -> bool,
+    {
+        if self.right.is_empty() {
+            return;
+        }
+
+        let mut search = Search {
+            operands: &self.right,
+            operators,
+            prune_negative: operators.iter().all(|op| op.preserves_non_negative())
+                && self.right.iter().all(|v| *v >= 0),
+            ops: Vec::with_capacity(self.right.len() - 1),
+            prefix: Vec::with_capacity(self.right.len() - 1),
+            f: &mut f,
+        };
+
+        search.unwind(self.right.len(), self.left);
+    }
+
     pub fn is_valid_combined_unwind_dfs(&self) -> (i64, i64) {
         let mut p1_valid = false;
         let mut p2_valid = false;
@@ -103,7 +466,7 @@ impl Equation {
         let idx = remaining - 1;
         let v = self.right[idx];
 
-        if head % v == 0 {
+        if v != 0 && head % v == 0 {
             self._is_valid_combined_unwind_dfs(idx, head / v, used_concat, p1_valid, p2_valid);
             if *p1_valid {
                 return;
@@ -125,6 +488,114 @@ impl Equation {
     }
 }
 
+/// State for searching backwards through an equation for operator
+/// combinations, using an arbitrary operator set.
+struct Search<'s, 'a, F> {
+    operands: &'s [i64],
+    operators: &'s [&'a dyn Operator],
+    prune_negative: bool,
+    // built in reverse, as we unwind from the end
+    ops: Vec<&'a dyn Operator>,
+    // built forwards, for when an operator accepts any left-hand operand
+    prefix: Vec<&'a dyn Operator>,
+    f: &'s mut F,
+}
+
+impl<'a, F> Search<'_, 'a, F>
+where
+    F: FnMut(&[&'a dyn Operator]) -> bool,
+{
+    // Same unwinding as `_is_valid_combined_unwind_dfs`, but generalized over
+    // the operators and tracking the operators as we go. Returns false once we
+    // should stop.
+    fn unwind(&mut self, remaining: usize, head: i64) -> bool {
+        if remaining == 1 {
+            if head != self.operands[0] {
+                return true;
+            }
+
+            self.ops.reverse();
+            let keep_going = (self.f)(&self.ops);
+            self.ops.reverse();
+            return keep_going;
+        }
+
+        if self.prune_negative && head < 0 {
+            return true;
+        }
+
+        let idx = remaining - 1;
+        let v = self.operands[idx];
+
+        for op in self.operators.iter().copied() {
+            self.ops.push(op);
+            let keep_going = match op.unwind(head, v) {
+                Unwound::None => true,
+                Unwound::One(prev) => self.unwind(idx, prev),
+                Unwound::Range(range) => {
+                    // a big divisor gives a range far too wide to unwind one
+                    // value at a time, so if it's cheaper, try every way of
+                    // evaluating the operands before this one instead
+                    let width = range.start().abs_diff(*range.end()).saturating_add(1);
+                    let combos = (self.operators.len() as u64).checked_pow((idx - 1) as u32);
+                    if combos.is_some_and(|c| c < width) {
+                        self.forward(1, idx, self.operands[0], &range)
+                    } else {
+                        range.into_iter().all(|prev| self.unwind(idx, prev))
+                    }
+                }
+                Unwound::Any => self.forward(1, idx, self.operands[0], &(i64::MIN..=i64::MAX)),
+            };
+            self.ops.pop();
+
+            if !keep_going {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // Every evaluable combination of operators for the first `end` operands
+    // that lands in `accept` is a solution when combined with what we've
+    // unwound so far.
+    fn forward(
+        &mut self,
+        idx: usize,
+        end: usize,
+        value: i64,
+        accept: &RangeInclusive<i64>,
+    ) -> bool {
+        if idx == end {
+            if !accept.contains(&value) {
+                return true;
+            }
+
+            let full: Vec<_> = self
+                .prefix
+                .iter()
+                .copied()
+                .chain(self.ops.iter().rev().copied())
+                .collect();
+            return (self.f)(&full);
+        }
+
+        for op in self.operators.iter().copied() {
+            if let Some(next) = op.apply(value, self.operands[idx]) {
+                self.prefix.push(op);
+                let keep_going = self.forward(idx + 1, end, next, accept);
+                self.prefix.pop();
+
+                if !keep_going {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
 fn digits(val: i64) -> u8 {
     (val.checked_ilog10().unwrap_or(0) + 1) as u8
 }
@@ -182,4 +653,88 @@ mod tests {
         let solution = BridgeRepair::solve(input).unwrap();
         assert_eq!(solution, Solution::new(3749, 11387));
     }
+
+    #[test]
+    fn explain() {
+        let inst = BridgeRepair::from_str("3267: 81 40 27\n156: 15 6\n83: 17 5").unwrap();
+        let eqs = inst.equations();
+
+        let solutions = eqs[0].solutions(PART_ONE_OPERATORS);
+        assert_eq!(solutions.len(), 2);
+        assert!(solutions.iter().any(|s| s.to_string() == "81 + 40 * 27"));
+        assert!(solutions.iter().any(|s| s.to_string() == "81 * 40 + 27"));
+
+        assert!(eqs[1].solve(PART_ONE_OPERATORS).is_none());
+        assert_eq!(
+            eqs[1].solve(PART_TWO_OPERATORS).map(|s| s.to_string()),
+            Some("15 || 6".to_string())
+        );
+        assert_eq!(eqs[1].num_solutions(PART_TWO_OPERATORS), 1);
+
+        assert!(eqs[2].solve(PART_TWO_OPERATORS).is_none());
+        assert_eq!(eqs[2].num_solutions(PART_TWO_OPERATORS), 0);
+    }
+
+    #[test]
+    fn operator_sets() {
+        let inst = BridgeRepair::from_str("4: 10 4 2\n3: 7 2\n6: 3 5\n13: 6 5\n4: 9 0 4").unwrap();
+        let eqs = inst.equations();
+
+        let sub: &[&dyn Operator] = &[&Sub, &Add];
+        assert_eq!(eqs[0].solve(sub).unwrap().to_string(), "10 - 4 - 2");
+        assert!(!eqs[0].is_satisfiable(PART_TWO_OPERATORS));
+
+        let div: &[&dyn Operator] = &[&Div];
+        assert_eq!(eqs[1].solve(div).unwrap().to_string(), "7 / 2");
+
+        let xor: &[&dyn Operator] = &[&Xor];
+        assert_eq!(eqs[2].solve(xor).unwrap().to_string(), "3 ^ 5");
+
+        // 6 || 5 in binary is 110101
+        let binary = Concat::new(2);
+        let bin: &[&dyn Operator] = &[&binary];
+        assert!(!eqs[3].is_satisfiable(bin));
+        assert!(BridgeRepair::from_str("53: 6 5").unwrap().equations()[0].is_satisfiable(bin));
+
+        // multiplying by zero means anything before it works
+        let solutions = eqs[4].solutions(PART_ONE_OPERATORS);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].to_string(), "9 * 0 + 4");
+
+        // unwinding a division by a million can't try every possible dividend
+        let big = BridgeRepair::from_str(
+            "2: 2000000 1000000 1000000 1000000 1000000 1000000 1000000\n3: 1000 3000 1000000",
+        )
+        .unwrap();
+        let ops: &[&dyn Operator] = &[&Add, &Mul, &Div];
+        for eq in big.equations() {
+            // every combination, the slow way
+            let n = eq.operands().len() - 1;
+            let expected = (0..ops.len().pow(n as u32))
+                .filter(|combo| {
+                    let mut value = Some(eq.operands()[0]);
+                    let mut combo = *combo;
+                    for v in eq.operands()[1..].iter() {
+                        value = value.and_then(|l| ops[combo % ops.len()].apply(l, *v));
+                        combo /= ops.len();
+                    }
+                    value == Some(eq.target())
+                })
+                .count();
+
+            assert!(expected > 0);
+            assert_eq!(eq.num_solutions(ops), expected);
+        }
+        assert_eq!(
+            big.equations()[1].solve(ops).unwrap().to_string(),
+            "1000 * 3000 / 1000000"
+        );
+
+        // for the puzzle operators, the general solver agrees with the fast one
+        for eq in eqs.iter().filter(|eq| !eq.operands().contains(&0)) {
+            let (p1, p2) = eq.is_valid_combined_unwind_dfs();
+            assert_eq!(p1 != 0, eq.is_satisfiable(PART_ONE_OPERATORS));
+            assert_eq!(p2 != 0, eq.is_satisfiable(PART_TWO_OPERATORS));
+        }
+    }
 }
@@ -1,5 +1,9 @@
-use std::{collections::hash_map::Entry, str::FromStr};
+use std::{
+    collections::{hash_map::Entry, BTreeMap},
+    str::FromStr,
+};
 
+use anyhow::bail;
 use aoc_plumbing::Problem;
 use aoc_std::geometry::Point2D;
 use itertools::Itertools;
@@ -8,51 +12,158 @@ use rustc_hash::FxHashMap;
 
 #[derive(Debug, Clone)]
 pub struct ResonantCollinearity {
-    antennas: FxHashMap<u8, Vec<Point2D<i8>>>,
-    size: i8,
+    antennas: FxHashMap<u8, Vec<Point2D<i32>>>,
+    width: i32,
+    height: i32,
 }
 
 impl FromStr for ResonantCollinearity {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut antennas: FxHashMap<u8, Vec<Point2D<i8>>> = FxHashMap::default();
+        let mut antennas: FxHashMap<u8, Vec<Point2D<i32>>> = FxHashMap::default();
 
-        let mut size = 0;
+        let mut width = None;
+        let mut height = 0;
         for (r, line) in s.trim().lines().enumerate() {
-            size = line.len() as i8;
+            match width {
+                None => width = Some(line.len()),
+                Some(w) if w != line.len() => bail!("row {} has a different width", r),
+                _ => {}
+            }
+
+            height = r + 1;
+
             for (c, ch) in line.chars().enumerate() {
                 if ch != '.' {
                     match antennas.entry(ch as u8) {
                         Entry::Occupied(mut occupied_entry) => {
-                            occupied_entry.get_mut().push((c as i8, r as i8).into());
+                            occupied_entry.get_mut().push((c as i32, r as i32).into());
                         }
                         Entry::Vacant(vacant_entry) => {
                             let s = vacant_entry.insert(Vec::default());
-                            s.push((c as i8, r as i8).into());
+                            s.push((c as i32, r as i32).into());
                         }
                     }
                 }
             }
         }
 
-        Ok(Self { antennas, size })
+        let width = width.unwrap_or_default();
+        if width > i32::MAX as usize || height > i32::MAX as usize {
+            bail!("map is too large");
+        }
+
+        Ok(Self {
+            antennas,
+            width: width as i32,
+            height: height as i32,
+        })
     }
 }
 
 impl ResonantCollinearity {
-    pub fn compute_antinodes(&self) -> usize {
-        let mut antinodes = AntinodeGrid::default();
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.height as usize
+    }
 
-        for antennas in self.antennas.values() {
-            self.compute_antinodes_for(antennas, &mut antinodes);
+    fn in_bounds(&self, point: &Point2D<i32>) -> bool {
+        point.x >= 0 && point.x < self.width && point.y >= 0 && point.y < self.height
+    }
+
+    /// Every antinode location from the part one model, with the antenna
+    /// pairs that produce it, ordered by row then column.
+    pub fn antinodes(&self) -> Vec<Antinode> {
+        let mut map = AntinodeMap::default();
+        self.fill_antinodes(&mut map);
+        map.into_antinodes()
+    }
+
+    /// Every antinode location when accounting for resonant harmonics, with
+    /// the antenna pairs that produce it, ordered by row then column.
+    pub fn line_antinodes(&self) -> Vec<Antinode> {
+        let mut map = AntinodeMap::default();
+        self.fill_line_antinodes(&mut map);
+        map.into_antinodes()
+    }
+
+    /// The number of distinct antinode locations produced by each frequency.
+    pub fn frequency_totals(antinodes: &[Antinode]) -> BTreeMap<u8, usize> {
+        let mut totals = BTreeMap::default();
+        for antinode in antinodes.iter() {
+            let mut frequencies: Vec<_> = antinode.sources.iter().map(|s| s.frequency).collect();
+            frequencies.sort_unstable();
+            frequencies.dedup();
+            for frequency in frequencies {
+                *totals.entry(frequency).or_default() += 1;
+            }
+        }
+        totals
+    }
+
+    /// Render the map with `#` marking each antinode not already occupied by
+    /// an antenna.
+    pub fn render(&self, antinodes: &[Antinode]) -> String {
+        let mut grid = vec![vec![b'.'; self.width()]; self.height()];
+
+        for antinode in antinodes.iter() {
+            grid[antinode.location.y as usize][antinode.location.x as usize] = b'#';
+        }
+
+        for (frequency, antennas) in self.antennas.iter() {
+            for antenna in antennas.iter() {
+                grid[antenna.y as usize][antenna.x as usize] = *frequency;
+            }
         }
 
+        let mut out = String::with_capacity((self.width() + 1) * self.height());
+        for row in grid {
+            out.extend(row.into_iter().map(|b| b as char));
+            out.push('\n');
+        }
+
+        out
+    }
+
+    // most maps fit in the fixed size grid, which is quite a bit faster
+    fn fits_small_grid(&self) -> bool {
+        self.width <= AntinodeGrid::MAX_WIDTH && self.height <= AntinodeGrid::MAX_HEIGHT
+    }
+
+    pub fn compute_antinodes(&self) -> usize {
+        if self.fits_small_grid() {
+            self._compute_antinodes(AntinodeGrid::default())
+        } else {
+            self._compute_antinodes(WideAntinodeGrid::new(self.width(), self.height()))
+        }
+    }
+
+    fn _compute_antinodes<A: Antinodes>(&self, mut antinodes: A) -> usize {
+        self.fill_antinodes(&mut antinodes);
         antinodes.count()
     }
 
-    fn compute_antinodes_for(&self, antennas: &[Point2D<i8>], antinodes: &mut AntinodeGrid) {
+    fn fill_antinodes<A: Antinodes>(&self, antinodes: &mut A) {
+        for (frequency, antennas) in self.antennas.iter() {
+            self.compute_antinodes_for(*frequency, antennas, antinodes);
+        }
+    }
+
+    fn compute_antinodes_for<A: Antinodes>(
+        &self,
+        frequency: u8,
+        antennas: &[Point2D<i32>],
+        antinodes: &mut A,
+    ) {
         for (a, b) in antennas.iter().tuple_combinations() {
+            let source = AntinodeSource {
+                frequency,
+                antennas: (*a, *b),
+            };
             let left = a.min(b);
             let right = a.max(b);
             let slope = right - left;
@@ -60,36 +171,46 @@ impl ResonantCollinearity {
             let candidate1 = left - slope;
             let candidate2 = right + slope;
 
-            if candidate1.x >= 0
-                && candidate1.x < self.size
-                && candidate1.y >= 0
-                && candidate1.y < self.size
-            {
-                antinodes.insert(&candidate1);
+            if self.in_bounds(&candidate1) {
+                antinodes.insert(&candidate1, &source);
             }
 
-            if candidate2.x >= 0
-                && candidate2.x < self.size
-                && candidate2.y >= 0
-                && candidate2.y < self.size
-            {
-                antinodes.insert(&candidate2);
+            if self.in_bounds(&candidate2) {
+                antinodes.insert(&candidate2, &source);
             }
         }
     }
 
     pub fn compute_line_antinodes(&self) -> usize {
-        let mut antinodes = AntinodeGrid::default();
-
-        for antennas in self.antennas.values() {
-            self.compute_line_antinodes_for(antennas, &mut antinodes);
+        if self.fits_small_grid() {
+            self._compute_line_antinodes(AntinodeGrid::default())
+        } else {
+            self._compute_line_antinodes(WideAntinodeGrid::new(self.width(), self.height()))
         }
+    }
 
+    fn _compute_line_antinodes<A: Antinodes>(&self, mut antinodes: A) -> usize {
+        self.fill_line_antinodes(&mut antinodes);
         antinodes.count()
     }
 
-    fn compute_line_antinodes_for(&self, antennas: &[Point2D<i8>], antinodes: &mut AntinodeGrid) {
+    fn fill_line_antinodes<A: Antinodes>(&self, antinodes: &mut A) {
+        for (frequency, antennas) in self.antennas.iter() {
+            self.compute_line_antinodes_for(*frequency, antennas, antinodes);
+        }
+    }
+
+    fn compute_line_antinodes_for<A: Antinodes>(
+        &self,
+        frequency: u8,
+        antennas: &[Point2D<i32>],
+        antinodes: &mut A,
+    ) {
         for (a, b) in antennas.iter().tuple_combinations() {
+            let source = AntinodeSource {
+                frequency,
+                antennas: (*a, *b),
+            };
             let left = a.min(b);
             let right = a.max(b);
             let mut slope = right - left;
@@ -112,32 +233,15 @@ impl ResonantCollinearity {
             let mut candidate1 = left - slope;
             let mut candidate2 = left + slope;
 
-            antinodes.insert(left);
-
-            loop {
-                if candidate1.x >= 0
-                    && candidate1.x < self.size
-                    && candidate1.y >= 0
-                    && candidate1.y < self.size
-                {
-                    antinodes.insert(&candidate1);
-                } else {
-                    break;
-                }
+            antinodes.insert(left, &source);
 
+            while self.in_bounds(&candidate1) {
+                antinodes.insert(&candidate1, &source);
                 candidate1 -= slope;
             }
 
-            loop {
-                if candidate2.x >= 0
-                    && candidate2.x < self.size
-                    && candidate2.y >= 0
-                    && candidate2.y < self.size
-                {
-                    antinodes.insert(&candidate2);
-                } else {
-                    break;
-                }
+            while self.in_bounds(&candidate2) {
+                antinodes.insert(&candidate2, &source);
                 candidate2 += slope;
             }
         }
@@ -162,6 +266,32 @@ impl Problem for ResonantCollinearity {
     }
 }
 
+/// A frequency and the pair of antennas of that frequency that produce an
+/// antinode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AntinodeSource {
+    pub frequency: u8,
+    pub antennas: (Point2D<i32>, Point2D<i32>),
+}
+
+/// An antinode location and everything that produces it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Antinode {
+    pub location: Point2D<i32>,
+    pub sources: Vec<AntinodeSource>,
+}
+
+/// Somewhere to record the locations of antinodes.
+pub trait Antinodes {
+    /// Record an antinode at `point`, which must be on the map, produced by
+    /// `source`.
+    fn insert(&mut self, point: &Point2D<i32>, source: &AntinodeSource);
+
+    /// The number of distinct antinode locations.
+    fn count(&self) -> usize;
+}
+
+/// Fixed size antinode storage for maps up to 64 columns and 50 rows.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct AntinodeGrid {
     grid: [u64; 50],
@@ -174,15 +304,82 @@ impl Default for AntinodeGrid {
 }
 
 impl AntinodeGrid {
-    pub fn insert(&mut self, point: &Point2D<i8>) {
+    pub const MAX_WIDTH: i32 = 64;
+    pub const MAX_HEIGHT: i32 = 50;
+}
+
+impl Antinodes for AntinodeGrid {
+    fn insert(&mut self, point: &Point2D<i32>, _source: &AntinodeSource) {
         self.grid[point.y as usize] |= 1 << point.x as usize;
     }
 
-    pub fn count(&self) -> usize {
+    fn count(&self) -> usize {
         self.grid.iter().map(|r| r.count_ones()).sum::<u32>() as usize
     }
 }
 
+/// Antinode storage for maps of any size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WideAntinodeGrid {
+    words_per_row: usize,
+    words: Vec<u64>,
+}
+
+impl WideAntinodeGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(64);
+        Self {
+            words_per_row,
+            words: vec![0; words_per_row * height],
+        }
+    }
+}
+
+impl Antinodes for WideAntinodeGrid {
+    fn insert(&mut self, point: &Point2D<i32>, _source: &AntinodeSource) {
+        let x = point.x as usize;
+        self.words[point.y as usize * self.words_per_row + x / 64] |= 1 << (x % 64);
+    }
+
+    fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+}
+
+/// Antinode storage that keeps track of what produced each antinode.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AntinodeMap {
+    antinodes: FxHashMap<(i32, i32), Vec<AntinodeSource>>,
+}
+
+impl AntinodeMap {
+    pub fn into_antinodes(self) -> Vec<Antinode> {
+        let mut antinodes: Vec<_> = self
+            .antinodes
+            .into_iter()
+            .map(|((x, y), sources)| Antinode {
+                location: (x, y).into(),
+                sources,
+            })
+            .collect();
+        antinodes.sort_unstable_by_key(|a| (a.location.y, a.location.x));
+        antinodes
+    }
+}
+
+impl Antinodes for AntinodeMap {
+    fn insert(&mut self, point: &Point2D<i32>, source: &AntinodeSource) {
+        self.antinodes
+            .entry((point.x, point.y))
+            .or_default()
+            .push(*source);
+    }
+
+    fn count(&self) -> usize {
+        self.antinodes.len()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use aoc_plumbing::Solution;
@@ -214,4 +411,96 @@ mod tests {
         let solution = ResonantCollinearity::solve(input).unwrap();
         assert_eq!(solution, Solution::new(14, 34));
     }
+
+    #[test]
+    fn rectangular() {
+        // the example, but with a bunch of extra empty columns and rows
+        let input = "............................
+........0...................
+.....0......................
+.......0....................
+....0.......................
+......A.....................
+............................
+............................
+........A...................
+.........A..................
+............................
+............................
+............................";
+        let solution = ResonantCollinearity::solve(input).unwrap();
+        assert_eq!(solution, Solution::new(14, 45));
+    }
+
+    #[test]
+    fn large() {
+        let mut input = vec![vec!['.'; 300]; 200];
+        input[10][10] = 'a';
+        input[20][20] = 'a';
+        input[100][200] = 'B';
+        input[150][250] = 'B';
+        let input = input
+            .into_iter()
+            .map(|row| row.into_iter().collect::<String>())
+            .join("\n");
+
+        let inst = ResonantCollinearity::from_str(&input).unwrap();
+        assert_eq!(inst.width(), 300);
+        assert_eq!(inst.height(), 200);
+        assert!(!inst.fits_small_grid());
+        // (0, 0) and (30, 30) for a, (150, 50) for B
+        assert_eq!(inst.compute_antinodes(), 3);
+        // two parallel diagonals, each crossing every row
+        assert_eq!(inst.compute_line_antinodes(), 400);
+
+        assert!(ResonantCollinearity::from_str("...\n..").is_err());
+    }
+
+    #[test]
+    fn enumerate_and_render() {
+        let input = "..........
+..........
+..........
+....a.....
+........a.
+.....a....
+..........
+......A...
+..........
+..........";
+        let inst = ResonantCollinearity::from_str(input).unwrap();
+        let antinodes = inst.antinodes();
+        assert_eq!(antinodes.len(), 4);
+
+        // the antinode under the A antenna comes from the first and last a's
+        let under = antinodes
+            .iter()
+            .find(|a| a.location == (6, 7).into())
+            .unwrap();
+        assert_eq!(
+            under.sources,
+            vec![AntinodeSource {
+                frequency: b'a',
+                antennas: ((4, 3).into(), (5, 5).into()),
+            }]
+        );
+
+        let totals = ResonantCollinearity::frequency_totals(&antinodes);
+        assert_eq!(totals.into_iter().collect::<Vec<_>>(), vec![(b'a', 4)]);
+
+        let expected = "..........
+...#......
+#.........
+....a.....
+........a.
+.....a....
+..#.......
+......A...
+..........
+..........
+";
+        assert_eq!(inst.render(&antinodes), expected);
+
+        assert_eq!(inst.line_antinodes().len(), inst.compute_line_antinodes());
+    }
 }
@@ -1,6 +1,8 @@
-use std::{collections::BTreeSet, str::FromStr};
+use std::{collections::BTreeSet, fmt::Write, str::FromStr};
 
+use anyhow::anyhow;
 use aoc_plumbing::Problem;
+use rustc_hash::FxHashMap;
 
 #[derive(Debug, Clone)]
 pub struct DiskFragmenter {
@@ -11,28 +13,50 @@ pub struct DiskFragmenter {
 impl FromStr for DiskFragmenter {
     type Err = anyhow::Error;
 
+    /// In addition to the puzzle's format of single digits, this accepts an
+    /// extended format where the sizes are separated by commas and/or
+    /// whitespace, allowing sizes larger than 9.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let sizes = if s.contains(|ch: char| ch == ',' || ch.is_whitespace()) {
+            s.split(|ch: char| ch == ',' || ch.is_whitespace())
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse::<usize>())
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            s.chars()
+                .map(|ch| {
+                    ch.to_digit(10)
+                        .map(|d| d as usize)
+                        .ok_or_else(|| anyhow!("invalid size: {}", ch))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
         let mut files = Vec::default();
         let mut free_buckets = vec![BTreeSet::default(); 10];
         let mut pos = 0;
-        for (idx, chunk) in s.trim().as_bytes().chunks(2).enumerate() {
+        for (idx, chunk) in sizes.chunks(2).enumerate() {
             let f = match chunk {
                 [size, free] => AocFile {
                     id: idx,
                     pos,
-                    size: (size - b'0') as usize,
-                    free: (free - b'0') as usize,
+                    size: *size,
+                    free: *free,
                 },
                 [size] => AocFile {
                     id: idx,
                     pos,
-                    size: (size - b'0') as usize,
+                    size: *size,
                     free: 0,
                 },
                 _ => unreachable!(),
             };
             pos += f.size + f.free;
             if f.free > 0 {
+                if f.free >= free_buckets.len() {
+                    free_buckets.resize(f.free + 1, BTreeSet::default());
+                }
                 free_buckets[f.free].insert(f.pos + f.size);
             }
             files.push(f);
@@ -44,6 +68,56 @@ impl FromStr for DiskFragmenter {
     }
 }
 
+impl DiskFragmenter {
+    /// The uncompacted disk, with the file id for each used block.
+    pub fn blocks(&self) -> Vec<Option<usize>> {
+        let len = self
+            .files
+            .last()
+            .map(|f| f.pos + f.size)
+            .unwrap_or_default();
+        let mut disk = vec![None; len];
+        for f in self.files.iter() {
+            disk[f.pos..(f.pos + f.size)].fill(Some(f.id));
+        }
+        disk
+    }
+
+    /// Start compacting the disk with the given strategy. The returned
+    /// iterator yields each move as it is made.
+    pub fn compaction<S: CompactionStrategy>(&self, mut strategy: S) -> Compaction<S> {
+        let disk = self.blocks();
+        strategy.init(&disk, &self.files);
+
+        Compaction { disk, strategy }
+    }
+
+    /// The disk after compacting with the given strategy.
+    pub fn compact<S: CompactionStrategy>(&self, strategy: S) -> Vec<Option<usize>> {
+        let mut compaction = self.compaction(strategy);
+        while compaction.next().is_some() {}
+        compaction.into_disk()
+    }
+
+    /// Compact the disk with the given strategy and summarize the result.
+    pub fn report<S: CompactionStrategy>(&self, strategy: S) -> CompactionReport {
+        let mut compaction = self.compaction(strategy);
+        let mut moves = 0;
+        let mut blocks_moved = 0;
+        for m in compaction.by_ref() {
+            moves += 1;
+            blocks_moved += m.len;
+        }
+
+        CompactionReport {
+            checksum: checksum(compaction.disk()),
+            moves,
+            blocks_moved,
+            fragmentation: Fragmentation::of(compaction.disk()),
+        }
+    }
+}
+
 impl Problem for DiskFragmenter {
     const DAY: usize = 9;
     const TITLE: &'static str = "disk fragmenter";
@@ -144,6 +218,24 @@ pub struct AocFile {
 }
 
 impl AocFile {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Where the file starts on the uncompacted disk.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The free blocks following the file on the uncompacted disk.
+    pub fn free(&self) -> usize {
+        self.free
+    }
+
     pub fn take(&mut self, amount: usize) -> usize {
         if amount > self.size {
             let taken = self.size;
@@ -160,10 +252,365 @@ impl AocFile {
     }
 
     pub fn checksum(&self, pos: usize) -> usize {
+        if self.size == 0 {
+            return 0;
+        }
+
         self.id * (pos + pos + self.size - 1) * self.size / 2
     }
 }
 
+/// Some number of contiguous blocks belonging to file `id` moving from `from`
+/// to `to`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub id: usize,
+    pub from: usize,
+    pub to: usize,
+    pub len: usize,
+}
+
+/// A way of choosing which blocks to move to compact a disk.
+pub trait CompactionStrategy {
+    /// Prepare to compact `disk`, which contains `files`.
+    fn init(&mut self, disk: &[Option<usize>], files: &[AocFile]);
+
+    /// Choose the next move to make on `disk`, or `None` if compaction is
+    /// complete. The move is applied to the disk by the caller.
+    fn next_move(&mut self, disk: &[Option<usize>]) -> Option<Move>;
+}
+
+/// Move individual blocks from the end of the disk to the leftmost free block
+/// (part one).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BlockLevel {
+    head: usize,
+    tail: usize,
+}
+
+impl CompactionStrategy for BlockLevel {
+    fn init(&mut self, disk: &[Option<usize>], _files: &[AocFile]) {
+        self.head = 0;
+        self.tail = disk.len();
+    }
+
+    fn next_move(&mut self, disk: &[Option<usize>]) -> Option<Move> {
+        while self.head < self.tail && disk[self.head].is_some() {
+            self.head += 1;
+        }
+
+        while self.tail > self.head && disk[self.tail - 1].is_none() {
+            self.tail -= 1;
+        }
+
+        if self.head >= self.tail {
+            return None;
+        }
+
+        // move as much of the last file as will fit in the first gap
+        let id = disk[self.tail - 1]?;
+        let free = disk[self.head..self.tail]
+            .iter()
+            .take_while(|b| b.is_none())
+            .count();
+        let run = disk[self.head..self.tail]
+            .iter()
+            .rev()
+            .take_while(|b| **b == Some(id))
+            .count();
+        let len = free.min(run);
+
+        Some(Move {
+            id,
+            from: self.tail - len,
+            to: self.head,
+            len,
+        })
+    }
+}
+
+/// How to pick between the free spans a whole file could be moved to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Fit {
+    /// The leftmost span (part two).
+    #[default]
+    First,
+    /// The smallest span, favoring the leftmost on ties.
+    Best,
+    /// The largest span, favoring the leftmost on ties.
+    Worst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Span {
+    pos: usize,
+    len: usize,
+}
+
+/// Move whole files, in decreasing id order, to a free span to their left
+/// that fits them, if there is one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct WholeFile {
+    fit: Fit,
+    files: Vec<AocFile>,
+    free: Vec<Span>,
+}
+
+impl WholeFile {
+    pub fn new(fit: Fit) -> Self {
+        Self {
+            fit,
+            ..Default::default()
+        }
+    }
+
+    pub fn first_fit() -> Self {
+        Self::new(Fit::First)
+    }
+
+    pub fn best_fit() -> Self {
+        Self::new(Fit::Best)
+    }
+
+    pub fn worst_fit() -> Self {
+        Self::new(Fit::Worst)
+    }
+}
+
+impl CompactionStrategy for WholeFile {
+    fn init(&mut self, _disk: &[Option<usize>], files: &[AocFile]) {
+        self.files = files.to_vec();
+        self.free = files
+            .iter()
+            .filter(|f| f.free > 0)
+            .map(|f| Span {
+                pos: f.pos + f.size,
+                len: f.free,
+            })
+            .collect();
+    }
+
+    fn next_move(&mut self, _disk: &[Option<usize>]) -> Option<Move> {
+        while let Some(f) = self.files.pop() {
+            if f.size == 0 {
+                continue;
+            }
+
+            // the spans are ordered by position, and we only ever move left,
+            // so the space a file vacates is never useful
+            let mut candidates = self
+                .free
+                .iter()
+                .enumerate()
+                .take_while(|(_, s)| s.pos < f.pos)
+                .filter(|(_, s)| s.len >= f.size);
+
+            let chosen = match self.fit {
+                Fit::First => candidates.next(),
+                // min_by_key and max_by_key disagree on which of equal
+                // elements they return, so be explicit about it
+                Fit::Best => candidates.min_by_key(|(_, s)| s.len),
+                Fit::Worst => candidates.min_by_key(|(_, s)| usize::MAX - s.len),
+            };
+
+            let Some((idx, _)) = chosen else {
+                continue;
+            };
+
+            let span = &mut self.free[idx];
+            let to = span.pos;
+            span.pos += f.size;
+            span.len -= f.size;
+            if span.len == 0 {
+                self.free.remove(idx);
+            }
+
+            return Some(Move {
+                id: f.id,
+                from: f.pos,
+                to,
+                len: f.size,
+            });
+        }
+
+        None
+    }
+}
+
+/// Move whole files, in increasing id order, to the rightmost free span to
+/// their right that fits them, packed against the end of that span.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ToEnd {
+    files: Vec<AocFile>,
+    free: Vec<Span>,
+}
+
+impl CompactionStrategy for ToEnd {
+    fn init(&mut self, _disk: &[Option<usize>], files: &[AocFile]) {
+        // reversed, so we can pop the lowest id
+        self.files = files.iter().rev().copied().collect();
+        self.free = files
+            .iter()
+            .filter(|f| f.free > 0)
+            .map(|f| Span {
+                pos: f.pos + f.size,
+                len: f.free,
+            })
+            .collect();
+    }
+
+    fn next_move(&mut self, _disk: &[Option<usize>]) -> Option<Move> {
+        while let Some(f) = self.files.pop() {
+            if f.size == 0 {
+                continue;
+            }
+
+            // the mirror image of `WholeFile`, we only ever move right, so
+            // the space a file vacates is never useful
+            let Some(idx) = self
+                .free
+                .iter()
+                .rposition(|s| s.pos > f.pos && s.len >= f.size)
+            else {
+                continue;
+            };
+
+            let span = &mut self.free[idx];
+            span.len -= f.size;
+            let to = span.pos + span.len;
+            if span.len == 0 {
+                self.free.remove(idx);
+            }
+
+            return Some(Move {
+                id: f.id,
+                from: f.pos,
+                to,
+                len: f.size,
+            });
+        }
+
+        None
+    }
+}
+
+/// A disk part way through being compacted.
+#[derive(Debug, Clone)]
+pub struct Compaction<S> {
+    disk: Vec<Option<usize>>,
+    strategy: S,
+}
+
+impl<S> Compaction<S> {
+    /// The current state of the disk.
+    pub fn disk(&self) -> &[Option<usize>] {
+        &self.disk
+    }
+
+    pub fn into_disk(self) -> Vec<Option<usize>> {
+        self.disk
+    }
+}
+
+impl<S: CompactionStrategy> Iterator for Compaction<S> {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let m = self.strategy.next_move(&self.disk)?;
+        self.disk[m.from..(m.from + m.len)].fill(None);
+        self.disk[m.to..(m.to + m.len)].fill(Some(m.id));
+        Some(m)
+    }
+}
+
+/// How fragmented a disk is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Fragmentation {
+    /// Files whose blocks are split into more than one run.
+    pub fragmented_files: usize,
+    /// Runs of free blocks before the last used block.
+    pub free_gaps: usize,
+    /// Free blocks before the last used block.
+    pub interior_free: usize,
+    /// The longest run of free blocks before the last used block.
+    pub largest_gap: usize,
+}
+
+impl Fragmentation {
+    pub fn of(disk: &[Option<usize>]) -> Self {
+        let end = disk
+            .iter()
+            .rposition(|b| b.is_some())
+            .map(|p| p + 1)
+            .unwrap_or_default();
+
+        let mut out = Self::default();
+        let mut runs: FxHashMap<usize, usize> = FxHashMap::default();
+        let mut gap = 0;
+        let mut prev = None;
+
+        for block in disk[..end].iter().copied() {
+            match block {
+                Some(id) => {
+                    if gap > 0 {
+                        out.free_gaps += 1;
+                        out.largest_gap = out.largest_gap.max(gap);
+                        gap = 0;
+                    }
+
+                    if prev != Some(id) {
+                        *runs.entry(id).or_default() += 1;
+                    }
+                }
+                None => {
+                    out.interior_free += 1;
+                    gap += 1;
+                }
+            }
+
+            prev = block;
+        }
+
+        out.fragmented_files = runs.values().filter(|r| **r > 1).count();
+
+        out
+    }
+}
+
+/// The outcome of compacting a disk with a particular strategy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CompactionReport {
+    pub checksum: usize,
+    pub moves: usize,
+    pub blocks_moved: usize,
+    pub fragmentation: Fragmentation,
+}
+
+/// The checksum of a disk.
+pub fn checksum(disk: &[Option<usize>]) -> usize {
+    disk.iter()
+        .enumerate()
+        .map(|(pos, id)| pos * id.unwrap_or_default())
+        .sum()
+}
+
+/// Render a disk in the style of the puzzle text, with `.` for free blocks.
+/// File ids larger than a single digit are wrapped in parentheses.
+pub fn render(disk: &[Option<usize>]) -> String {
+    let mut out = String::with_capacity(disk.len());
+    for block in disk.iter() {
+        match block {
+            Some(id) if *id < 10 => out.push((b'0' + *id as u8) as char),
+            Some(id) => {
+                // writing to a string cannot fail
+                let _ = write!(out, "({})", id);
+            }
+            None => out.push('.'),
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use aoc_plumbing::Solution;
@@ -184,4 +631,121 @@ mod tests {
         let solution = DiskFragmenter::solve(input).unwrap();
         assert_eq!(solution, Solution::new(1928, 2858));
     }
+
+    #[test]
+    fn layouts() {
+        let inst = DiskFragmenter::from_str("2333133121414131402").unwrap();
+        assert_eq!(
+            render(&inst.blocks()),
+            "00...111...2...333.44.5555.6666.777.888899"
+        );
+
+        let blocks = inst.compact(BlockLevel::default());
+        assert_eq!(
+            render(&blocks),
+            "0099811188827773336446555566.............."
+        );
+        assert_eq!(checksum(&blocks), 1928);
+
+        let mut compaction = inst.compaction(WholeFile::first_fit());
+        assert_eq!(
+            compaction.next(),
+            Some(Move {
+                id: 9,
+                from: 40,
+                to: 2,
+                len: 2
+            })
+        );
+        assert_eq!(
+            render(compaction.disk()),
+            "0099.111...2...333.44.5555.6666.777.8888.."
+        );
+        assert_eq!(compaction.by_ref().count(), 3);
+        let files = compaction.into_disk();
+        assert_eq!(render(&files), "00992111777.44.333....5555.6666.....8888..");
+        assert_eq!(checksum(&files), 2858);
+
+        let mut disk = vec![None; 12];
+        disk[1] = Some(12);
+        assert_eq!(render(&disk), ".(12)..........");
+    }
+
+    #[test]
+    fn strategies() {
+        let inst = DiskFragmenter::from_str("2333133121414131402").unwrap();
+
+        let report = inst.report(BlockLevel::default());
+        assert_eq!(report.checksum, 1928);
+        assert_eq!(report.fragmentation.interior_free, 0);
+        // 6 and 8 get split up
+        assert_eq!(report.fragmentation.fragmented_files, 2);
+
+        let report = inst.report(WholeFile::first_fit());
+        assert_eq!(report.checksum, 2858);
+        assert_eq!(report.moves, 4);
+        assert_eq!(report.blocks_moved, 8);
+        assert_eq!(report.fragmentation.free_gaps, 5);
+
+        // best fit prefers the exact gap between 1 and 2 for 3
+        let inst = DiskFragmenter::from_str("1311121").unwrap();
+        assert_eq!(render(&inst.blocks()), "0...1.2..3");
+        assert_eq!(render(&inst.compact(WholeFile::first_fit())), "0321......");
+        assert_eq!(render(&inst.compact(WholeFile::best_fit())), "021..3....");
+
+        // worst fit prefers the larger gap after 1
+        let inst = DiskFragmenter::from_str("1113101").unwrap();
+        assert_eq!(render(&inst.compact(WholeFile::first_fit())), "0312....");
+        assert_eq!(render(&inst.compact(WholeFile::worst_fit())), "01.32...");
+    }
+
+    #[test]
+    fn to_end() {
+        let inst = DiskFragmenter::from_str("2333133121414131402").unwrap();
+        assert_eq!(
+            render(&inst.compact(ToEnd::default())),
+            "........111..00333.44.5555.6666.7772888899"
+        );
+        let report = inst.report(ToEnd::default());
+        assert_eq!(
+            report,
+            CompactionReport {
+                checksum: 4173,
+                moves: 3,
+                blocks_moved: 6,
+                fragmentation: Fragmentation {
+                    fragmented_files: 0,
+                    free_gaps: 6,
+                    interior_free: 14,
+                    largest_gap: 8,
+                },
+            }
+        );
+
+        // strategies can see where every file starts and how big it is
+        let files: Vec<_> = inst
+            .files
+            .iter()
+            .map(|f| (f.id(), f.pos(), f.size(), f.free()))
+            .collect();
+        assert_eq!(files[0], (0, 0, 2, 3));
+        assert_eq!(files[9], (9, 40, 2, 0));
+    }
+
+    #[test]
+    fn extended_format() {
+        let inst = DiskFragmenter::from_str("2, 12, 11, 3\n4").unwrap();
+        assert_eq!(render(&inst.blocks()), "00............11111111111...2222");
+        let solution = DiskFragmenter::solve("2, 12, 11, 3\n4").unwrap();
+        assert_eq!(
+            solution.part_one,
+            checksum(&inst.compact(BlockLevel::default()))
+        );
+        assert_eq!(
+            solution.part_two,
+            checksum(&inst.compact(WholeFile::first_fit()))
+        );
+
+        assert!(DiskFragmenter::from_str("12a").is_err());
+    }
 }
@@ -9,6 +9,8 @@ use rustc_hash::FxHashSet;
 
 #[derive(Debug, Clone)]
 pub struct HoofIt {
+    grid: DigitGrid,
+    rules: TrailRules,
     p1: u16,
     p2: u16,
 }
@@ -21,11 +23,262 @@ impl FromStr for HoofIt {
 
         let Point2D { x: p1, y: p2 } = Self::sum_trailheads(&grid);
 
-        Ok(Self { p1, p2 })
+        Ok(Self {
+            grid,
+            rules: TrailRules::default(),
+            p1,
+            p2,
+        })
     }
 }
 
+/// Describes what makes a valid hiking trail.
+///
+/// Trails start on a cell of height `start`, take steps whose change in height
+/// is one of `steps` and finish as soon as they reach a cell of height `end`.
+/// If the steps allow a trail to revisit a height, trails are not allowed to
+/// visit the same cell twice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrailRules {
+    pub steps: Vec<i8>,
+    pub start: u8,
+    pub end: u8,
+    pub diagonal: bool,
+}
+
+impl Default for TrailRules {
+    fn default() -> Self {
+        Self {
+            steps: vec![1],
+            start: 0,
+            end: 9,
+            diagonal: false,
+        }
+    }
+}
+
+impl TrailRules {
+    const CARDINAL: [(isize, isize); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+    const DIAGONAL: [(isize, isize); 4] = [(-1, 1), (1, 1), (1, -1), (-1, -1)];
+
+    pub fn new(steps: impl IntoIterator<Item = i8>, start: u8, end: u8) -> Self {
+        Self {
+            steps: steps.into_iter().collect(),
+            start,
+            end,
+            diagonal: false,
+        }
+    }
+
+    pub fn with_diagonal(mut self, diagonal: bool) -> Self {
+        self.diagonal = diagonal;
+        self
+    }
+
+    pub fn allows(&self, from: u8, to: u8) -> bool {
+        let delta = to as i16 - from as i16;
+        self.steps.iter().any(|s| *s as i16 == delta)
+    }
+
+    /// If every step moves in the same direction, no trail can loop back on
+    /// itself, which lets us memoize trail counts.
+    pub fn is_monotone(&self) -> bool {
+        self.steps.iter().all(|s| *s > 0) || self.steps.iter().all(|s| *s < 0)
+    }
+
+    /// The cells reachable in a single step from `loc`, in the usual order
+    /// (clockwise from north, cardinal directions first).
+    pub fn next_steps<'a>(
+        &'a self,
+        grid: &'a DigitGrid,
+        loc: Location,
+    ) -> impl Iterator<Item = Location> + 'a {
+        let height = grid.locations[loc.row][loc.col];
+        let diagonal: &[(isize, isize)] = if self.diagonal { &Self::DIAGONAL } else { &[] };
+
+        Self::CARDINAL
+            .iter()
+            .chain(diagonal.iter())
+            .filter_map(move |(dr, dc)| {
+                let n = Location::new(
+                    loc.row.checked_add_signed(*dr)?,
+                    loc.col.checked_add_signed(*dc)?,
+                );
+                grid.get(&n).filter(|v| self.allows(height, **v)).map(|_| n)
+            })
+    }
+}
+
+/// A single trailhead, with the peaks it can reach.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trailhead {
+    pub location: Location,
+    /// The number of distinct peaks reachable from this trailhead.
+    pub score: usize,
+    /// The number of distinct trails starting at this trailhead.
+    pub rating: usize,
+    /// The reachable peaks, in row-major order.
+    pub peaks: Vec<Location>,
+}
+
 impl HoofIt {
+    pub fn grid(&self) -> &DigitGrid {
+        &self.grid
+    }
+
+    pub fn rules(&self) -> &TrailRules {
+        &self.rules
+    }
+
+    /// Use a different set of rules for the trail APIs. This does not affect
+    /// the puzzle answers.
+    pub fn with_rules(mut self, rules: TrailRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Every trailhead in row-major order with its score, rating and peaks.
+    pub fn trailheads(&self) -> Vec<Trailhead> {
+        let mut memo = Grid::from(vec![vec![None; self.grid.width()]; self.grid.height()]);
+        let mut out = Vec::new();
+
+        for r in 0..self.grid.height() {
+            for c in 0..self.grid.width() {
+                if self.grid.locations[r][c] != self.rules.start {
+                    continue;
+                }
+
+                let location = Location::new(r, c);
+                let peaks = self.reachable_peaks(location);
+
+                out.push(Trailhead {
+                    location,
+                    score: peaks.len(),
+                    rating: self.count_trails(location, &mut memo),
+                    peaks,
+                });
+            }
+        }
+
+        out
+    }
+
+    /// The sum of the scores of every trailhead.
+    pub fn total_score(&self) -> usize {
+        self.trailheads().iter().map(|t| t.score).sum()
+    }
+
+    /// The sum of the ratings of every trailhead.
+    pub fn total_rating(&self) -> usize {
+        self.trailheads().iter().map(|t| t.rating).sum()
+    }
+
+    /// Iterates over every distinct hiking trail, from trailhead to peak.
+    pub fn trails(&self) -> Trails<'_> {
+        let mut stack = Vec::new();
+        for r in (0..self.grid.height()).rev() {
+            for c in (0..self.grid.width()).rev() {
+                if self.grid.locations[r][c] == self.rules.start {
+                    stack.push(vec![Location::new(r, c)]);
+                }
+            }
+        }
+
+        Trails {
+            grid: &self.grid,
+            rules: &self.rules,
+            stack,
+        }
+    }
+
+    /// The rating of every cell, i.e. the number of distinct trails from that
+    /// cell to a peak, as if the cell were a trailhead.
+    ///
+    /// If the rules allow loops, this has to enumerate the trails from every
+    /// cell, which gets very expensive on large maps.
+    pub fn ratings(&self) -> Grid<usize> {
+        let mut memo = Grid::from(vec![vec![None; self.grid.width()]; self.grid.height()]);
+        let mut ratings = Grid::from(vec![vec![0; self.grid.width()]; self.grid.height()]);
+
+        for r in 0..self.grid.height() {
+            for c in 0..self.grid.width() {
+                ratings.locations[r][c] = self.count_trails(Location::new(r, c), &mut memo);
+            }
+        }
+
+        ratings
+    }
+
+    /// Renders the per-cell ratings as a heatmap. Ratings are shown as base 36
+    /// digits, with `.` for cells that cannot reach a peak and `+` for ratings
+    /// that don't fit in a single character.
+    pub fn render_heatmap(&self) -> String {
+        let ratings = self.ratings();
+        let mut out = String::with_capacity((ratings.width() + 1) * ratings.height());
+        for row in ratings.locations.iter() {
+            for rating in row.iter() {
+                out.push(match *rating {
+                    0 => '.',
+                    v => u32::try_from(v)
+                        .ok()
+                        .and_then(|v| char::from_digit(v, 36))
+                        .unwrap_or('+'),
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn reachable_peaks(&self, origin: Location) -> Vec<Location> {
+        let mut seen = FxHashSet::default();
+        let mut stack = vec![origin];
+        let mut peaks = Vec::new();
+        seen.insert(origin);
+
+        while let Some(cur) = stack.pop() {
+            if self.grid.locations[cur.row][cur.col] == self.rules.end {
+                peaks.push(cur);
+                continue;
+            }
+
+            for n in self.rules.next_steps(&self.grid, cur) {
+                if seen.insert(n) {
+                    stack.push(n);
+                }
+            }
+        }
+
+        peaks.sort();
+        peaks
+    }
+
+    fn count_trails(&self, pos: Location, memo: &mut Grid<Option<usize>>) -> usize {
+        if !self.rules.is_monotone() {
+            // the memo would be wrong here, as the count depends on the path
+            // we took to get here
+            return Trails {
+                grid: &self.grid,
+                rules: &self.rules,
+                stack: vec![vec![pos]],
+            }
+            .count();
+        }
+
+        if self.grid.locations[pos.row][pos.col] == self.rules.end {
+            return 1;
+        }
+
+        if let Some(Some(cached)) = memo.get(&pos) {
+            return *cached;
+        }
+
+        let next: Vec<_> = self.rules.next_steps(&self.grid, pos).collect();
+        let total = next.into_iter().map(|n| self.count_trails(n, memo)).sum();
+        memo.locations[pos.row][pos.col] = Some(total);
+        total
+    }
+
     pub fn sum_trailheads(grid: &DigitGrid) -> Point2D<u16> {
         let mut cache = Grid::from(vec![vec![None; grid.width()]; grid.height()]);
 
@@ -107,6 +360,41 @@ impl HoofIt {
     }
 }
 
+/// Depth-first iterator over hiking trails. See [HoofIt::trails].
+#[derive(Debug, Clone)]
+pub struct Trails<'a> {
+    grid: &'a DigitGrid,
+    rules: &'a TrailRules,
+    stack: Vec<Vec<Location>>,
+}
+
+impl Iterator for Trails<'_> {
+    type Item = Vec<Location>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(trail) = self.stack.pop() {
+            let cur = *trail.last()?;
+            if self.grid.locations[cur.row][cur.col] == self.rules.end {
+                return Some(trail);
+            }
+
+            // push in reverse so we visit neighbors in the usual order
+            let next: Vec<_> = self
+                .rules
+                .next_steps(self.grid, cur)
+                .filter(|n| self.rules.is_monotone() || !trail.contains(n))
+                .collect();
+            for n in next.into_iter().rev() {
+                let mut t = trail.clone();
+                t.push(n);
+                self.stack.push(t);
+            }
+        }
+
+        None
+    }
+}
+
 #[derive(Debug, Clone)]
 struct CacheEntry {
     unique: FxHashSet<Location>,
@@ -145,6 +433,85 @@ mod tests {
         assert_eq!(solution, Solution::new(624, 1483));
     }
 
+    const EXAMPLE: &str = "89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732";
+
+    #[test]
+    fn trailheads_and_trails() {
+        let problem = HoofIt::from_str(EXAMPLE).unwrap();
+        let heads = problem.trailheads();
+        assert_eq!(heads.len(), 9);
+        assert_eq!(
+            heads.iter().map(|t| t.score).collect::<Vec<_>>(),
+            vec![5, 6, 5, 3, 1, 3, 5, 3, 5]
+        );
+        assert_eq!(
+            heads.iter().map(|t| t.rating).collect::<Vec<_>>(),
+            vec![20, 24, 10, 4, 1, 4, 5, 8, 5]
+        );
+        assert!(heads.iter().all(|t| t.peaks.len() == t.score));
+
+        let trails: Vec<_> = problem.trails().collect();
+        assert_eq!(trails.len(), 81);
+        for trail in trails.iter() {
+            assert_eq!(trail.len(), 10);
+            for (i, w) in trail.windows(2).enumerate() {
+                assert_eq!(w[0].manhattan_dist(&w[1]), 1);
+                assert_eq!(problem.grid().get(&w[0]), Some(&(i as u8)));
+            }
+        }
+
+        let ratings = problem.ratings();
+        for head in heads.iter() {
+            assert_eq!(ratings.get(&head.location), Some(&head.rating));
+        }
+    }
+
+    #[test]
+    fn trail_rules() {
+        let problem = HoofIt::from_str(EXAMPLE).unwrap();
+        assert_eq!((problem.total_score(), problem.total_rating()), (36, 81));
+
+        let cases = [
+            (TrailRules::new([1, 2], 0, 9), (43, 88)),
+            (TrailRules::default().with_diagonal(true), (43, 93)),
+            (TrailRules::new([-1], 9, 0), (36, 81)),
+        ];
+        for (rules, expected) in cases {
+            let problem = problem.clone().with_rules(rules);
+            assert_eq!((problem.total_score(), problem.total_rating()), expected);
+            assert_eq!(problem.trails().count(), expected.1);
+        }
+
+        // these rules allow loops
+        let problem = HoofIt::from_str("012\n101\n210").unwrap();
+        let rules = TrailRules::new([-1, 0, 1], 0, 2);
+        assert!(!rules.is_monotone());
+        let problem = problem.with_rules(rules.clone());
+        assert_eq!((problem.total_score(), problem.total_rating()), (6, 32));
+        let problem = problem.with_rules(rules.with_diagonal(true));
+        assert_eq!((problem.total_score(), problem.total_rating()), (6, 424));
+        assert_eq!(problem.trails().count(), 424);
+    }
+
+    #[test]
+    fn heatmap() {
+        let problem = HoofIt::from_str(
+            "0123
+1234
+8765
+9876",
+        )
+        .unwrap();
+        assert_eq!(problem.render_heatmap(), "gc84\n4444\n1234\n1111\n");
+    }
+
     #[test]
     fn example() {
         let input = "89010123
@@ -1,11 +1,18 @@
-use std::str::FromStr;
+use std::{
+    fmt::{Debug, Display},
+    str::FromStr,
+};
 
+use anyhow::bail;
 use aoc_plumbing::Problem;
 // use cached::proc_macro::cached;
-use rustc_hash::{FxBuildHasher, FxHashMap};
+use num::{traits::CheckedAdd, BigUint, One, Zero};
+use rustc_hash::{FxBuildHasher, FxHashMap, FxHashSet};
+use serde::Serialize;
 
 #[derive(Debug, Clone)]
 pub struct PlutoniumPebbles {
+    stones: Vec<u64>,
     p1: usize,
     p2: usize,
 }
@@ -14,13 +21,14 @@ impl FromStr for PlutoniumPebbles {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stones = s
+            .split_whitespace()
+            .map(|v| v.parse::<u64>())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
         let mut cur = FxHashMap::with_capacity_and_hasher(4000, FxBuildHasher);
-        cur.extend(
-            s.trim()
-                .split(' ')
-                .map(|v| v.parse::<u64>().map(|a| (a, 1)))
-                .collect::<std::result::Result<FxHashMap<u64, usize>, _>>()?,
-        );
+        for stone in stones.iter() {
+            *cur.entry(*stone).or_default() += 1;
+        }
         let mut next = FxHashMap::with_capacity_and_hasher(4000, FxBuildHasher);
         let mut p1 = 0;
         for i in 0..75 {
@@ -51,10 +59,354 @@ impl FromStr for PlutoniumPebbles {
         // let p1 = cur.keys().map(|k| dfs_cached(*k, 25)).sum();
         // let p2 = cur.keys().map(|k| dfs_cached(*k, 75)).sum();
 
-        Ok(Self { p1, p2 })
+        Ok(Self { stones, p1, p2 })
+    }
+}
+
+impl PlutoniumPebbles {
+    /// The stones we started with.
+    pub fn stones(&self) -> &[u64] {
+        &self.stones
+    }
+}
+
+/// What a stone turns into after a blink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Blink {
+    One(u64),
+    Split(u64, u64),
+}
+
+/// A rule for how a stone changes when we blink. Rules are tried in order and
+/// the first one that applies wins.
+pub trait StoneRule: Debug + Send + Sync {
+    /// The stone(s) `stone` becomes, or `None` if this rule doesn't apply.
+    /// Errors if the rule applies but the new stone doesn't fit in a `u64`.
+    fn apply(&self, stone: u64) -> anyhow::Result<Option<Blink>>;
+}
+
+/// `0` becomes `1`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ZeroToOne;
+
+impl StoneRule for ZeroToOne {
+    fn apply(&self, stone: u64) -> anyhow::Result<Option<Blink>> {
+        Ok((stone == 0).then_some(Blink::One(1)))
     }
 }
 
+/// Stones with an even number of digits split into their left and right
+/// halves.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SplitEvenDigits;
+
+impl StoneRule for SplitEvenDigits {
+    fn apply(&self, stone: u64) -> anyhow::Result<Option<Blink>> {
+        Ok(split_even_digits(stone).map(|(l, r)| Blink::Split(l, r)))
+    }
+}
+
+/// Stones with an odd number of digits (at least three) split with the
+/// shorter half on the left, so `12345` becomes `12` and `345`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SplitOddDigits;
+
+impl StoneRule for SplitOddDigits {
+    fn apply(&self, stone: u64) -> anyhow::Result<Option<Blink>> {
+        let digits = stone.checked_ilog10().unwrap_or(0) + 1;
+        if digits % 2 == 1 && digits > 1 {
+            let divisor = 10_u64.pow(digits / 2 + 1);
+            Ok(Some(Blink::Split(stone / divisor, stone % divisor)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Multiply the stone by `factor`. Always applies, and errors if the stone
+/// gets too big.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Multiply {
+    pub factor: u64,
+}
+
+impl Multiply {
+    pub const PUZZLE: Self = Self { factor: 2024 };
+}
+
+impl StoneRule for Multiply {
+    fn apply(&self, stone: u64) -> anyhow::Result<Option<Blink>> {
+        match stone.checked_mul(self.factor) {
+            Some(v) => Ok(Some(Blink::One(v))),
+            None => bail!(
+                "Stone {} overflows when multiplied by {}",
+                stone,
+                self.factor
+            ),
+        }
+    }
+}
+
+/// The rules from the puzzle.
+pub const DEFAULT_RULES: &[&dyn StoneRule] = &[&ZeroToOne, &SplitEvenDigits, &Multiply::PUZZLE];
+
+/// A number of stones, which may not fit in a `usize`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StoneCount {
+    Small(usize),
+    Big(BigUint),
+}
+
+impl StoneCount {
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Self::Small(v) => Some(*v),
+            Self::Big(_) => None,
+        }
+    }
+
+    pub fn to_biguint(&self) -> BigUint {
+        match self {
+            Self::Small(v) => BigUint::from(*v),
+            Self::Big(v) => v.clone(),
+        }
+    }
+}
+
+impl From<BigUint> for StoneCount {
+    fn from(value: BigUint) -> Self {
+        match usize::try_from(&value) {
+            Ok(v) => Self::Small(v),
+            Err(_) => Self::Big(value),
+        }
+    }
+}
+
+impl Serialize for StoneCount {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Small(v) => serializer.serialize_u64(*v as u64),
+            Self::Big(v) => serializer.serialize_str(&v.to_string()),
+        }
+    }
+}
+
+impl Display for StoneCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Small(v) => write!(f, "{}", v),
+            Self::Big(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// The number of stones after `blinks` blinks with the puzzle rules.
+pub fn count_after(stones: &[u64], blinks: usize) -> anyhow::Result<StoneCount> {
+    count_after_with(stones, blinks, DEFAULT_RULES)
+}
+
+/// The number of stones after `blinks` blinks with the given `rules`.
+///
+/// This counts with `usize` and only falls back to big integers if that
+/// overflows. Errors if there's a stone none of the rules apply to.
+pub fn count_after_with(
+    stones: &[u64],
+    blinks: usize,
+    rules: &[&dyn StoneRule],
+) -> anyhow::Result<StoneCount> {
+    if let Some(count) = try_count_after::<usize>(stones, blinks, rules)? {
+        return Ok(StoneCount::Small(count));
+    }
+
+    try_count_after::<BigUint>(stones, blinks, rules)?
+        .map(StoneCount::Big)
+        .ok_or_else(|| anyhow::anyhow!("big integers should not overflow"))
+}
+
+/// Returns `None` if the count overflows `T`.
+fn try_count_after<T>(
+    stones: &[u64],
+    blinks: usize,
+    rules: &[&dyn StoneRule],
+) -> anyhow::Result<Option<T>>
+where
+    T: Clone + Zero + One + CheckedAdd,
+{
+    let mut cur: FxHashMap<u64, T> = FxHashMap::default();
+    for stone in stones.iter() {
+        let e = cur.entry(*stone).or_insert_with(T::zero);
+        let Some(v) = e.checked_add(&T::one()) else {
+            return Ok(None);
+        };
+        *e = v;
+    }
+
+    let mut next = FxHashMap::default();
+    for _ in 0..blinks {
+        for (stone, count) in cur.drain() {
+            let (left, right) = blink_stone(stone, rules)?;
+            for s in std::iter::once(left).chain(right) {
+                let e = next.entry(s).or_insert_with(T::zero);
+                let Some(v) = e.checked_add(&count) else {
+                    return Ok(None);
+                };
+                *e = v;
+            }
+        }
+
+        std::mem::swap(&mut cur, &mut next);
+    }
+
+    let mut total = T::zero();
+    for count in cur.values() {
+        let Some(v) = total.checked_add(count) else {
+            return Ok(None);
+        };
+        total = v;
+    }
+
+    Ok(Some(total))
+}
+
+fn blink_stone(stone: u64, rules: &[&dyn StoneRule]) -> anyhow::Result<(u64, Option<u64>)> {
+    for rule in rules.iter() {
+        match rule.apply(stone)? {
+            Some(Blink::One(v)) => return Ok((v, None)),
+            Some(Blink::Split(l, r)) => return Ok((l, Some(r))),
+            None => {}
+        }
+    }
+
+    bail!("No rule applies to stone {}", stone)
+}
+
+/// The stones present after a given number of blinks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Distribution {
+    pub blink: usize,
+    pub total: StoneCount,
+    pub distinct: usize,
+    /// The most common stones and how many of each there are, most common
+    /// first. Ties go to the smaller stone.
+    pub most_common: Vec<(u64, StoneCount)>,
+}
+
+/// The distribution after every blink from `0` to `blinks` (inclusive), keeping
+/// the `top` most common stones for each.
+pub fn distributions(
+    stones: &[u64],
+    blinks: usize,
+    rules: &[&dyn StoneRule],
+    top: usize,
+) -> anyhow::Result<Vec<Distribution>> {
+    // we want the raw numbers here, so just pay for the big ints
+    let mut cur: FxHashMap<u64, BigUint> = FxHashMap::default();
+    for stone in stones.iter() {
+        *cur.entry(*stone).or_default() += 1_u32;
+    }
+
+    let mut out = Vec::with_capacity(blinks + 1);
+    let mut next = FxHashMap::default();
+    for blink in 0..=blinks {
+        out.push(Distribution::of(blink, &cur, top));
+
+        if blink == blinks {
+            break;
+        }
+
+        for (stone, count) in cur.drain() {
+            let (left, right) = blink_stone(stone, rules)?;
+            if let Some(right) = right {
+                *next.entry(right).or_default() += &count;
+            }
+            *next.entry(left).or_default() += count;
+        }
+
+        std::mem::swap(&mut cur, &mut next);
+    }
+
+    Ok(out)
+}
+
+impl Distribution {
+    fn of(blink: usize, counts: &FxHashMap<u64, BigUint>, top: usize) -> Self {
+        let total: BigUint = counts.values().sum();
+        let mut common: Vec<_> = counts.iter().collect();
+        common.sort_unstable_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        Self {
+            blink,
+            total: StoneCount::from(total),
+            distinct: counts.len(),
+            most_common: common
+                .into_iter()
+                .take(top)
+                .map(|(stone, count)| (*stone, StoneCount::from(count.clone())))
+                .collect(),
+        }
+    }
+}
+
+/// Serializes a series of distributions. Counts that don't fit in a `usize`
+/// are written as strings.
+pub fn distributions_json(series: &[Distribution]) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(series)?)
+}
+
+/// When the set of distinct stones stops changing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Stabilization {
+    /// The first blink after which the set of distinct stones is the same
+    /// forever.
+    pub blink: usize,
+    /// The stones present from `blink` onwards, sorted.
+    pub stable: Vec<u64>,
+    /// Every stone value that can ever appear, sorted.
+    pub reachable: Vec<u64>,
+}
+
+/// Blinks (ignoring counts) until the set of distinct stones stops changing,
+/// giving up after `limit` blinks.
+///
+/// The set after a blink only depends on the set before it, so once a blink
+/// leaves it the same it's fixed. Sets that cycle through several values
+/// never stop changing, so those give up too.
+pub fn stabilization(
+    stones: &[u64],
+    rules: &[&dyn StoneRule],
+    limit: usize,
+) -> anyhow::Result<Option<Stabilization>> {
+    let mut cur: FxHashSet<u64> = stones.iter().copied().collect();
+    let mut reachable = cur.clone();
+
+    for blink in 0..limit {
+        let mut next = FxHashSet::default();
+        next.reserve(cur.len());
+        for stone in cur.iter() {
+            let (left, right) = blink_stone(*stone, rules)?;
+            next.insert(left);
+            next.extend(right);
+        }
+
+        if next == cur {
+            let mut stable: Vec<_> = cur.into_iter().collect();
+            stable.sort_unstable();
+            let mut reachable: Vec<_> = reachable.into_iter().collect();
+            reachable.sort_unstable();
+            return Ok(Some(Stabilization {
+                blink,
+                stable,
+                reachable,
+            }));
+        }
+
+        reachable.extend(next.iter().copied());
+        cur = next;
+    }
+
+    Ok(None)
+}
+
 // #[cached]
 // fn dfs_cached(stone: u64, blinks: usize) -> usize {
 //     if blinks == 0 {
@@ -113,6 +465,110 @@ mod tests {
         assert_eq!(solution, Solution::new(189547, 224577979481346));
     }
 
+    #[test]
+    fn arbitrary_blinks() {
+        let stones = PlutoniumPebbles::from_str("125 17")
+            .unwrap()
+            .stones()
+            .to_vec();
+        assert_eq!(count_after(&stones, 0).unwrap(), StoneCount::Small(2));
+        assert_eq!(count_after(&stones, 6).unwrap(), StoneCount::Small(22));
+        assert_eq!(count_after(&stones, 25).unwrap(), StoneCount::Small(55312));
+
+        let big = count_after(&stones, 500).unwrap();
+        assert!(big.as_usize().is_none());
+        assert!(big.to_biguint() > BigUint::from(usize::MAX));
+
+        // one more blink has to give us more stones
+        let bigger = count_after(&stones, 501).unwrap();
+        assert!(bigger.to_biguint() > big.to_biguint());
+    }
+
+    #[test]
+    fn rule_variants() {
+        assert_eq!(
+            SplitOddDigits.apply(12345).unwrap(),
+            Some(Blink::Split(12, 345))
+        );
+        assert_eq!(SplitOddDigits.apply(7).unwrap(), None);
+
+        // 1 -> 3 -> 9 -> 27 -> 2, 7
+        let rules: &[&dyn StoneRule] = &[&SplitEvenDigits, &Multiply { factor: 3 }];
+        assert_eq!(
+            count_after_with(&[1], 4, rules).unwrap(),
+            StoneCount::Small(2)
+        );
+
+        // 125 -> 1, 25 -> 2024, 2, 5 -> 20, 24, 4048, 10120
+        let rules: &[&dyn StoneRule] = &[
+            &ZeroToOne,
+            &SplitEvenDigits,
+            &SplitOddDigits,
+            &Multiply::PUZZLE,
+        ];
+        assert_eq!(
+            count_after_with(&[125], 3, rules).unwrap(),
+            StoneCount::Small(4)
+        );
+
+        // nothing applies to 3
+        let err = count_after_with(&[3], 1, &[&ZeroToOne]).unwrap_err();
+        assert_eq!(err.to_string(), "No rule applies to stone 3");
+
+        // overflowing isn't the same as nothing applying
+        let err = count_after_with(&[u64::MAX / 2], 1, &[&Multiply { factor: 3 }]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("Stone {} overflows when multiplied by 3", u64::MAX / 2)
+        );
+    }
+
+    #[test]
+    fn distribution_series() {
+        let series = distributions(&[125, 17], 6, DEFAULT_RULES, 2).unwrap();
+        assert_eq!(series.len(), 7);
+        assert_eq!(
+            series.iter().map(|d| d.total.clone()).collect::<Vec<_>>(),
+            [2, 3, 4, 5, 9, 13, 22].map(StoneCount::Small)
+        );
+
+        // 2097446912 14168 4048 2 0 2 4 40 48 2024 40 48 80 96 2 8 6 7 6 0 3 2
+        assert_eq!(series[6].distinct, 15);
+        assert_eq!(
+            series[6].most_common,
+            vec![(2, StoneCount::Small(4)), (0, StoneCount::Small(2))]
+        );
+
+        let json = distributions_json(&series[..2]).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"blink":0,"total":2,"distinct":2,"most_common":[[17,1],[125,1]]},{"blink":1,"total":3,"distinct":3,"most_common":[[1,1],[7,1]]}]"#
+        );
+
+        let big = distributions(&[0], 500, DEFAULT_RULES, 1).unwrap();
+        assert!(distributions_json(&big[500..])
+            .unwrap()
+            .contains(r#""total":""#));
+    }
+
+    #[test]
+    fn stabilizes() {
+        let stable = stabilization(&[125, 17], DEFAULT_RULES, 1000)
+            .unwrap()
+            .unwrap();
+        assert!(stable.stable.len() <= stable.reachable.len());
+        assert!(stable.reachable.contains(&125));
+
+        let series = distributions(&[125, 17], stable.blink + 5, DEFAULT_RULES, 0).unwrap();
+        for d in series[stable.blink..].iter() {
+            assert_eq!(d.distinct, stable.stable.len());
+        }
+
+        // this one just grows forever
+        let rules: &[&dyn StoneRule] = &[&Multiply { factor: 2 }];
+        assert!(stabilization(&[1], rules, 10).unwrap().is_none());
+    }
+
     #[test]
     fn example() {
         let input = "125 17";
@@ -2,7 +2,7 @@ use std::{collections::VecDeque, str::FromStr};
 
 use aoc_plumbing::Problem;
 use aoc_std::{
-    collections::{bitset::BitSet192, CharGrid},
+    collections::{CharGrid, Grid},
     directions::Cardinal,
     geometry::Location,
 };
@@ -15,6 +15,7 @@ const LR: u8 = Cardinal::South as u8 | Cardinal::East as u8;
 
 #[derive(Debug, Clone)]
 pub struct GardenGroups {
+    grid: CharGrid,
     p1: u64,
     p2: u64,
 }
@@ -27,20 +28,273 @@ impl FromStr for GardenGroups {
 
         let (p1, p2) = Self::process(&grid);
 
-        Ok(Self { p1, p2 })
+        Ok(Self { grid, p1, p2 })
+    }
+}
+
+/// A single connected region of plants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub id: usize,
+    pub label: char,
+    /// The cells in the region, in row-major order.
+    pub cells: Vec<Location>,
+    pub area: u64,
+    pub perimeter: u64,
+    pub sides: u64,
+    /// The top-left and bottom-right corners of the bounding box (inclusive).
+    pub bounds: (Location, Location),
+    /// The number of pockets of other cells completely surrounded by this
+    /// region.
+    pub holes: usize,
+    /// The id of the innermost region that completely surrounds this one.
+    pub enclosed_by: Option<usize>,
+}
+
+impl Region {
+    pub fn price(&self) -> u64 {
+        self.area * self.perimeter
+    }
+
+    pub fn discounted_price(&self) -> u64 {
+        self.area * self.sides
+    }
+}
+
+/// All the regions of a map, in the order of their top-left-most cell.
+#[derive(Debug, Clone)]
+pub struct Regions {
+    regions: Vec<Region>,
+    ids: Grid<usize>,
+}
+
+impl Regions {
+    pub fn iter(&self) -> std::slice::Iter<'_, Region> {
+        self.regions.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Region> {
+        self.regions.get(id)
+    }
+
+    /// The region containing `location`.
+    pub fn region_at(&self, location: &Location) -> Option<&Region> {
+        self.ids.get(location).and_then(|id| self.regions.get(*id))
+    }
+}
+
+impl IntoIterator for Regions {
+    type Item = Region;
+    type IntoIter = std::vec::IntoIter<Region>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.regions.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Regions {
+    type Item = &'a Region;
+    type IntoIter = std::slice::Iter<'a, Region>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.regions.iter()
     }
 }
 
 impl GardenGroups {
+    pub fn grid(&self) -> &CharGrid {
+        &self.grid
+    }
+
+    /// Extracts every region, along with its holes and what encloses it.
+    pub fn regions(&self) -> Regions {
+        let grid = &self.grid;
+        let mut ids = Grid::from(
+            grid.locations
+                .iter()
+                .map(|row| vec![usize::MAX; row.len()])
+                .collect::<Vec<_>>(),
+        );
+        let mut queue = VecDeque::with_capacity(1000);
+        let mut regions = Vec::new();
+
+        for r in 0..grid.height() {
+            for c in 0..grid.locations[r].len() {
+                if ids.locations[r][c] == usize::MAX {
+                    let region = Self::flood_region(
+                        &mut queue,
+                        grid,
+                        Location::new(r, c),
+                        regions.len(),
+                        &mut ids,
+                    );
+                    regions.push(region);
+                }
+            }
+        }
+
+        // (hole size, enclosing region) for every enclosed region
+        let mut enclosures: Vec<Option<(usize, usize)>> = vec![None; regions.len()];
+        for region in regions.iter_mut() {
+            let holes = Self::holes(region);
+            region.holes = holes.len();
+
+            for hole in holes {
+                let mut inside: Vec<_> = hole.iter().map(|l| ids.locations[l.row][l.col]).collect();
+                inside.sort_unstable();
+                inside.dedup();
+
+                // the smallest hole containing a region is the innermost one
+                for id in inside {
+                    if enclosures[id]
+                        .map(|(size, _)| hole.len() < size)
+                        .unwrap_or(true)
+                    {
+                        enclosures[id] = Some((hole.len(), region.id));
+                    }
+                }
+            }
+        }
+
+        for (region, enclosure) in regions.iter_mut().zip(enclosures) {
+            region.enclosed_by = enclosure.map(|(_, id)| id);
+        }
+
+        Regions { regions, ids }
+    }
+
+    fn flood_region(
+        queue: &mut VecDeque<Location>,
+        grid: &CharGrid,
+        pos: Location,
+        id: usize,
+        ids: &mut Grid<usize>,
+    ) -> Region {
+        let label = grid.locations[pos.row][pos.col];
+        queue.clear();
+        queue.push_back(pos);
+        ids.locations[pos.row][pos.col] = id;
+
+        let mut cells = Vec::new();
+        let mut perimeter = 0;
+        let mut sides = 0;
+        let mut min = pos;
+        let mut max = pos;
+
+        while let Some(next) = queue.pop_front() {
+            cells.push(next);
+            min.row = min.row.min(next.row);
+            min.col = min.col.min(next.col);
+            max.row = max.row.max(next.row);
+            max.col = max.col.max(next.col);
+
+            let mut num_edges = 4;
+            let mut dir_map = 0_u8;
+            for (dir, neighbor_loc, neighbor_value) in grid.cardinal_neighbors(&next) {
+                if neighbor_value == &label {
+                    dir_map |= dir as u8;
+                    num_edges -= 1;
+                    if ids.locations[neighbor_loc.row][neighbor_loc.col] == usize::MAX {
+                        ids.locations[neighbor_loc.row][neighbor_loc.col] = id;
+                        queue.push_back(neighbor_loc);
+                    }
+                }
+            }
+
+            perimeter += num_edges;
+            sides += Self::corners(grid, &next, label, dir_map);
+        }
+
+        cells.sort_unstable();
+
+        Region {
+            id,
+            label,
+            area: cells.len() as u64,
+            cells,
+            perimeter,
+            sides,
+            bounds: (min, max),
+            holes: 0,
+            enclosed_by: None,
+        }
+    }
+
+    /// The cells of every hole in `region`.
+    fn holes(region: &Region) -> Vec<Vec<Location>> {
+        const FREE: u8 = 0;
+        const FILLED: u8 = 1;
+        const VISITED: u8 = 2;
+
+        // we work in a box one cell bigger than the bounds on every side, so
+        // we know everything reachable from the corner of it is outside
+        let (min, max) = region.bounds;
+        let height = max.row - min.row + 3;
+        let width = max.col - min.col + 3;
+        let mut marks = vec![vec![FREE; width]; height];
+        for cell in region.cells.iter() {
+            marks[cell.row - min.row + 1][cell.col - min.col + 1] = FILLED;
+        }
+
+        let fill = |marks: &mut [Vec<u8>], start: Location| -> Vec<Location> {
+            let mut out = vec![start];
+            let mut stack = vec![start];
+            marks[start.row][start.col] = VISITED;
+            while let Some(cur) = stack.pop() {
+                for (_, n) in cur.cardinal_neighbors() {
+                    if n.row < height && n.col < width && marks[n.row][n.col] == FREE {
+                        marks[n.row][n.col] = VISITED;
+                        out.push(n);
+                        stack.push(n);
+                    }
+                }
+            }
+            out
+        };
+
+        fill(&mut marks, Location::new(0, 0));
+
+        let mut holes = Vec::new();
+        for r in 1..(height - 1) {
+            for c in 1..(width - 1) {
+                if marks[r][c] == FREE {
+                    let mut hole = fill(&mut marks, Location::new(r, c));
+                    for loc in hole.iter_mut() {
+                        loc.row = loc.row + min.row - 1;
+                        loc.col = loc.col + min.col - 1;
+                    }
+                    holes.push(hole);
+                }
+            }
+        }
+
+        holes
+    }
+
     pub fn process(grid: &CharGrid) -> (u64, u64) {
-        let mut seen = WideGrid::new(grid.height());
+        // rows aren't guaranteed to be the same length
+        let width = grid
+            .locations
+            .iter()
+            .map(|row| row.len())
+            .max()
+            .unwrap_or(0);
+        let mut seen = WideGrid::new(width, grid.height());
         let mut queue = VecDeque::with_capacity(1000);
 
         let mut p1_total = 0;
         let mut p2_total = 0;
 
         for r in 0..grid.height() {
-            for c in 0..grid.width() {
+            for c in 0..grid.locations[r].len() {
                 let loc = Location::new(r, c);
                 if !seen.contains(&loc) {
                     let (corners, perimeter, area) = Self::corners_area_and_perimeter(
@@ -91,35 +345,34 @@ impl GardenGroups {
                 }
             }
 
-            // upper left
-            let ul = UL & dir_map;
-            if ul == 0 || (ul == UL && grid.locations[next.row - 1][next.col - 1] != label) {
-                total_corners += 1;
-            }
-
-            // upper right
-            let ur = UR & dir_map;
-            if ur == 0 || (ur == UR && grid.locations[next.row - 1][next.col + 1] != label) {
-                total_corners += 1;
-            }
-
-            // lower left
-            let ll = LL & dir_map;
-            if ll == 0 || (ll == LL && grid.locations[next.row + 1][next.col - 1] != label) {
-                total_corners += 1;
-            }
-
-            // lower right
-            let lr = LR & dir_map;
-            if lr == 0 || (lr == LR && grid.locations[next.row + 1][next.col + 1] != label) {
-                total_corners += 1;
-            }
-
+            total_corners += Self::corners(grid, &next, label, dir_map);
             perimeter += num_edges;
         }
 
         (total_corners, perimeter, area)
     }
+
+    /// The number of corners at `next`, given the directions of the
+    /// neighbors that share its label.
+    fn corners(grid: &CharGrid, next: &Location, label: char, dir_map: u8) -> u64 {
+        // anything off the edge of the map is never part of the region
+        let diagonal_differs = |dr: isize, dc: isize| {
+            next.row
+                .checked_add_signed(dr)
+                .zip(next.col.checked_add_signed(dc))
+                .and_then(|(row, col)| grid.get(&Location::new(row, col)))
+                .map(|v| *v != label)
+                .unwrap_or(true)
+        };
+
+        [(UL, -1, -1), (UR, -1, 1), (LL, 1, -1), (LR, 1, 1)]
+            .into_iter()
+            .filter(|(corner, dr, dc)| {
+                let shared = corner & dir_map;
+                shared == 0 || (shared == *corner && diagonal_differs(*dr, *dc))
+            })
+            .count() as u64
+    }
 }
 
 impl Problem for GardenGroups {
@@ -140,24 +393,30 @@ impl Problem for GardenGroups {
     }
 }
 
+/// A visited set for a grid, one bit per cell.
 #[derive(Debug, Clone)]
 pub struct WideGrid {
-    rows: Vec<BitSet192>,
+    words_per_row: usize,
+    words: Vec<u64>,
 }
 
 impl WideGrid {
-    pub fn new(height: usize) -> Self {
+    pub fn new(width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(64);
         Self {
-            rows: vec![BitSet192::ZERO; height],
+            words_per_row,
+            words: vec![0; words_per_row * height],
         }
     }
 
     pub fn insert(&mut self, location: &Location) {
-        self.rows[location.row].insert(location.col);
+        let idx = location.row * self.words_per_row + location.col / 64;
+        self.words[idx] |= 1 << (location.col % 64);
     }
 
     pub fn contains(&self, location: &Location) -> bool {
-        self.rows[location.row].contains(location.col)
+        let idx = location.row * self.words_per_row + location.col / 64;
+        self.words[idx] & (1 << (location.col % 64)) != 0
     }
 }
 
@@ -175,6 +434,113 @@ mod tests {
         assert_eq!(solution, Solution::new(1465968, 897702));
     }
 
+    #[test]
+    fn regions() {
+        let input = "AAAAAA
+AAABBA
+AAABBA
+ABBAAA
+ABBAAA
+AAAAAA";
+        let problem = GardenGroups::from_str(input).unwrap();
+        let regions = problem.regions();
+        assert_eq!(regions.len(), 3);
+
+        let outer = regions.get(0).unwrap();
+        assert_eq!(outer.label, 'A');
+        assert_eq!((outer.area, outer.perimeter, outer.sides), (28, 40, 12));
+        assert_eq!(outer.bounds, (Location::new(0, 0), Location::new(5, 5)));
+        assert_eq!(outer.holes, 2);
+        assert_eq!(outer.enclosed_by, None);
+
+        for id in [1, 2] {
+            let inner = regions.get(id).unwrap();
+            assert_eq!(inner.label, 'B');
+            assert_eq!((inner.area, inner.sides, inner.holes), (4, 4, 0));
+            assert_eq!(inner.enclosed_by, Some(0));
+        }
+
+        assert_eq!(regions.region_at(&Location::new(4, 2)).unwrap().id, 2);
+        assert_eq!(
+            regions.iter().map(|r| r.discounted_price()).sum::<u64>(),
+            368
+        );
+    }
+
+    #[test]
+    fn nested_regions() {
+        let input = "OOOOO
+OXXXO
+OXAXO
+OXXXO
+OOOOO
+ZZZZZ";
+        let problem = GardenGroups::from_str(input).unwrap();
+        let regions = problem.regions();
+        let labels: Vec<_> = regions
+            .iter()
+            .map(|r| (r.label, r.holes, r.enclosed_by))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ('O', 1, None),
+                ('X', 1, Some(0)),
+                ('A', 0, Some(1)),
+                ('Z', 0, None)
+            ]
+        );
+        assert_eq!(
+            regions.iter().map(|r| r.price()).sum::<u64>(),
+            problem.clone().part_one().unwrap()
+        );
+    }
+
+    #[test]
+    fn large_maps() {
+        // a single row wider than the old visited set could handle
+        let row = "A".repeat(1000);
+        let problem = GardenGroups::from_str(&row).unwrap();
+        assert_eq!(
+            (
+                problem.clone().part_one().unwrap(),
+                problem.clone().part_two().unwrap()
+            ),
+            (1000 * 2002, 1000 * 4)
+        );
+
+        // 1000x1000 checkerboard of 10x10 blocks, which all touch the border
+        // or their diagonal neighbors
+        let input = (0..1000)
+            .map(|r| {
+                (0..1000)
+                    .map(|c| if (r / 10 + c / 10) % 2 == 0 { 'A' } else { 'B' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let problem = GardenGroups::from_str(&input).unwrap();
+        assert_eq!(
+            (
+                problem.clone().part_one().unwrap(),
+                problem.clone().part_two().unwrap()
+            ),
+            (10_000 * 100 * 40, 10_000 * 100 * 4)
+        );
+        assert_eq!(problem.regions().len(), 10_000);
+    }
+
+    #[test]
+    fn wide_grid() {
+        let mut seen = WideGrid::new(130, 2);
+        seen.insert(&Location::new(1, 129));
+        seen.insert(&Location::new(0, 64));
+        assert!(seen.contains(&Location::new(1, 129)));
+        assert!(seen.contains(&Location::new(0, 64)));
+        assert!(!seen.contains(&Location::new(0, 129)));
+        assert!(!seen.contains(&Location::new(1, 64)));
+    }
+
     #[test]
     fn example() {
         let input = "RRRRIICCFF
@@ -1,5 +1,6 @@
 use std::str::FromStr;
 
+use anyhow::bail;
 use aoc_plumbing::Problem;
 use aoc_std::geometry::Point2D;
 use nom::{
@@ -25,6 +26,22 @@ impl FromStr for ClawContraption {
     }
 }
 
+impl ClawContraption {
+    pub fn machines(&self) -> &[Machine] {
+        &self.machines
+    }
+
+    /// The cost of winning every prize we can, with the prizes moved by
+    /// `offset`.
+    pub fn total_cost(&self, pricing: &Pricing, offset: i64) -> i64 {
+        self.machines
+            .iter()
+            .filter_map(|m| m.with_offset(offset).solve(pricing))
+            .map(|p| p.cost)
+            .sum()
+    }
+}
+
 impl Problem for ClawContraption {
     const DAY: usize = 13;
     const TITLE: &'static str = "claw contraption";
@@ -35,58 +52,719 @@ impl Problem for ClawContraption {
     type P2 = i64;
 
     fn part_one(&mut self) -> Result<Self::P1, Self::ProblemError> {
-        Ok(self.machines.iter().filter_map(|m| m.cost_small()).sum())
+        Ok(self.total_cost(&Pricing::PART_ONE, 0))
     }
 
     fn part_two(&mut self) -> Result<Self::P2, Self::ProblemError> {
-        Ok(self.machines.iter().filter_map(|m| m.cost_large()).sum())
+        Ok(self.total_cost(&Pricing::PART_TWO, PART_TWO_OFFSET))
     }
 }
 
+/// How far the prizes move in part two.
+pub const PART_TWO_OFFSET: i64 = 10_000_000_000_000;
+
+/// What each button costs to press, and how many times we're allowed to press
+/// it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Pricing {
+    pub cost_a: i64,
+    pub cost_b: i64,
+    pub limit_a: Option<i64>,
+    pub limit_b: Option<i64>,
+}
+
+impl Default for Pricing {
+    fn default() -> Self {
+        Self::PART_TWO
+    }
+}
+
+impl Pricing {
+    pub const PART_ONE: Self = Self {
+        cost_a: 3,
+        cost_b: 1,
+        limit_a: Some(100),
+        limit_b: Some(100),
+    };
+
+    pub const PART_TWO: Self = Self {
+        cost_a: 3,
+        cost_b: 1,
+        limit_a: None,
+        limit_b: None,
+    };
+
+    fn allows(&self, a: i64, b: i64) -> bool {
+        a >= 0
+            && b >= 0
+            && self.limit_a.map(|l| a <= l).unwrap_or(true)
+            && self.limit_b.map(|l| b <= l).unwrap_or(true)
+    }
+}
+
+/// The number of times each button was pressed to win a prize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Presses {
+    pub a: i64,
+    pub b: i64,
+    pub cost: i64,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct Machine {
-    a: Point2D<i64>,
-    b: Point2D<i64>,
-    prize: Point2D<i64>,
+pub struct Machine {
+    pub a: Point2D<i64>,
+    pub b: Point2D<i64>,
+    pub prize: Point2D<i64>,
 }
 
 impl Machine {
-    // a.x * n + b.x * m = prize.x
-    // a.y * m + b.y * m = prize.y
     pub fn cost_small(&self) -> Option<i64> {
+        self.solve(&Pricing::PART_ONE).map(|p| p.cost)
+    }
+
+    pub fn cost_large(&self) -> Option<i64> {
+        self.with_offset(PART_TWO_OFFSET)
+            .solve(&Pricing::PART_TWO)
+            .map(|p| p.cost)
+    }
+
+    /// The same machine with the prize moved `offset` along both axes.
+    pub fn with_offset(&self, offset: i64) -> Self {
+        Self {
+            prize: Point2D::new(self.prize.x + offset, self.prize.y + offset),
+            ..*self
+        }
+    }
+
+    /// The cheapest way to win the prize, if there is one.
+    // a.x * n + b.x * m = prize.x
+    // a.y * n + b.y * m = prize.y
+    pub fn solve(&self, pricing: &Pricing) -> Option<Presses> {
         let det = self.a.x * self.b.y - self.a.y * self.b.x;
-        if det == 0 {
+
+        let (n, m) = if det == 0 {
+            self.solve_collinear(pricing)?
+        } else {
+            // there's exactly one solution, so it's either valid or it's not
+            let n = self.prize.x * self.b.y - self.prize.y * self.b.x;
+            let m = self.a.x * self.prize.y - self.a.y * self.prize.x;
+
+            if n % det != 0 || m % det != 0 {
+                return None;
+            }
+
+            (n / det, m / det)
+        };
+
+        if !pricing.allows(n, m) {
             return None;
         }
 
-        let n = self.prize.x * self.b.y - self.prize.y * self.b.x;
-        let m = self.a.x * self.prize.y - self.a.y * self.prize.x;
+        Some(Presses {
+            a: n,
+            b: m,
+            cost: n * pricing.cost_a + m * pricing.cost_b,
+        })
+    }
+
+    // If the buttons move the claw along the same line, we only have one
+    // equation to work with, so we find every integer solution with extended
+    // GCD and pick the cheapest one within the limits.
+    fn solve_collinear(&self, pricing: &Pricing) -> Option<(i64, i64)> {
+        let cross = |u: Point2D<i64>, v: Point2D<i64>| {
+            u.x as i128 * v.y as i128 - u.y as i128 * v.x as i128
+        };
 
-        if n % det != 0 || m % det != 0 {
+        if cross(self.a, self.prize) != 0 || cross(self.b, self.prize) != 0 {
             return None;
         }
 
-        Some(n / det * 3 + m / det)
-    }
+        // pick an axis the buttons actually move along, at which point the
+        // other equation is redundant
+        let (u, v, w) = if self.a.x != 0 || self.b.x != 0 {
+            (self.a.x, self.b.x, self.prize.x)
+        } else if self.a.y != 0 || self.b.y != 0 {
+            (self.a.y, self.b.y, self.prize.y)
+        } else {
+            // neither button does anything
+            return (self.prize == Point2D::new(0, 0)).then_some((0, 0));
+        };
 
-    pub fn cost_large(&self) -> Option<i64> {
-        let prize = self.prize + 10_000_000_000_000;
-        let det = self.a.x * self.b.y - self.a.y * self.b.x;
-        if det == 0 {
+        let (u, v, w) = (u as i128, v as i128, w as i128);
+        let (g, x, y) = extended_gcd(u, v);
+        if w % g != 0 {
             return None;
         }
 
-        let n = prize.x * self.b.y - prize.y * self.b.x;
-        let m = self.a.x * prize.y - self.a.y * prize.x;
+        // every solution is (n0 + k * dn, m0 + k * dm) for some integer k
+        let (n0, m0) = (x * (w / g), y * (w / g));
+        let (dn, dm) = (v / g, -u / g);
 
-        if n % det != 0 || m % det != 0 {
+        let mut lo = i128::MIN;
+        let mut hi = i128::MAX;
+        constrain(&mut lo, &mut hi, n0, dn, pricing.limit_a)?;
+        constrain(&mut lo, &mut hi, m0, dm, pricing.limit_b)?;
+        if lo > hi {
             return None;
         }
 
-        Some(n / det * 3 + m / det)
+        // the cost is linear in k, so the cheapest is at one of the ends
+        let slope = pricing.cost_a as i128 * dn + pricing.cost_b as i128 * dm;
+        let k = match slope.cmp(&0) {
+            std::cmp::Ordering::Greater if lo > i128::MIN => lo,
+            std::cmp::Ordering::Less if hi < i128::MAX => hi,
+            std::cmp::Ordering::Equal => {
+                if lo > i128::MIN {
+                    lo
+                } else if hi < i128::MAX {
+                    hi
+                } else {
+                    0
+                }
+            }
+            // no cheapest solution
+            _ => return None,
+        };
+
+        let n = i64::try_from(n0 + k * dn).ok()?;
+        let m = i64::try_from(m0 + k * dm).ok()?;
+        Some((n, m))
+    }
+
+    /// The same machine expressed with the general model.
+    pub fn generalize(&self, pricing: &Pricing) -> GeneralMachine {
+        GeneralMachine {
+            buttons: vec![
+                Button {
+                    delta: vec![self.a.x, self.a.y],
+                    cost: pricing.cost_a,
+                    limit: pricing.limit_a,
+                },
+                Button {
+                    delta: vec![self.b.x, self.b.y],
+                    cost: pricing.cost_b,
+                    limit: pricing.limit_b,
+                },
+            ],
+            prize: vec![self.prize.x, self.prize.y],
+        }
+    }
+}
+
+/// A button that moves the claw by `delta` (in any number of dimensions).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Button {
+    pub delta: Vec<i64>,
+    pub cost: i64,
+    pub limit: Option<i64>,
+}
+
+/// The number of times each button was pressed in a [GeneralMachine].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ButtonPresses {
+    pub presses: Vec<i64>,
+    pub cost: i64,
+}
+
+/// A machine with any number of buttons.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GeneralMachine {
+    pub buttons: Vec<Button>,
+    pub prize: Vec<i64>,
+}
+
+impl GeneralMachine {
+    /// How many branches we're willing to explore before giving up.
+    const MAX_NODES: usize = 100_000;
+
+    /// The cheapest way to win the prize, if there is one.
+    ///
+    /// Two buttons in two dimensions use the closed form. Otherwise, we find
+    /// every integer combination of presses that hits the prize as
+    /// `x0 + K * t` for integer `t`, and then branch and bound over `t`. Costs
+    /// must not be negative.
+    pub fn solve(&self) -> anyhow::Result<Option<ButtonPresses>> {
+        let dims = self.prize.len();
+        if let Some(b) = self.buttons.iter().find(|b| b.delta.len() != dims) {
+            bail!(
+                "Button moves in {} dimensions, but the prize is in {}",
+                b.delta.len(),
+                dims
+            );
+        }
+
+        if self.buttons.iter().any(|b| b.cost < 0) {
+            bail!("Button costs cannot be negative");
+        }
+
+        if self.buttons.len() == 2 && dims == 2 {
+            let (a, b) = (&self.buttons[0], &self.buttons[1]);
+            let machine = Machine {
+                a: Point2D::new(a.delta[0], a.delta[1]),
+                b: Point2D::new(b.delta[0], b.delta[1]),
+                prize: Point2D::new(self.prize[0], self.prize[1]),
+            };
+            let pricing = Pricing {
+                cost_a: a.cost,
+                cost_b: b.cost,
+                limit_a: a.limit,
+                limit_b: b.limit,
+            };
+            return Ok(machine.solve(&pricing).map(|p| ButtonPresses {
+                presses: vec![p.a, p.b],
+                cost: p.cost,
+            }));
+        }
+
+        let Some(lattice) = Lattice::new(self) else {
+            return Ok(None);
+        };
+
+        let mut search = Search {
+            lattice: &lattice,
+            best: None,
+            nodes: 0,
+        };
+        search.branch(&mut Vec::new())?;
+
+        Ok(search.best.map(|(cost, t)| {
+            let presses = lattice.presses(&t).into_iter().map(|p| p as i64).collect();
+            ButtonPresses {
+                presses,
+                cost: cost as i64,
+            }
+        }))
     }
 }
 
+/// Every integer solution to `A * x = prize` is `x0 + K * t`, where `t` is
+/// any integer vector.
+#[derive(Debug, Clone)]
+struct Lattice {
+    x0: Vec<i128>,
+    /// One column per free dimension (so `x0.len()` rows).
+    kernel: Vec<Vec<i128>>,
+    costs: Vec<i128>,
+    limits: Vec<Option<i128>>,
+}
+
+impl Lattice {
+    fn new(machine: &GeneralMachine) -> Option<Self> {
+        let n = machine.buttons.len();
+        let dims = machine.prize.len();
+
+        // columns of `a` are the buttons. We apply unimodular column
+        // operations to both `a` and `u` until `a` is in column echelon form,
+        // so `u` ends up being the transform with `original * u == a`.
+        let mut a: Vec<Vec<i128>> = (0..dims)
+            .map(|d| machine.buttons.iter().map(|b| b.delta[d] as i128).collect())
+            .collect();
+        let mut u: Vec<Vec<i128>> = (0..n)
+            .map(|r| (0..n).map(|c| (r == c) as i128).collect())
+            .collect();
+
+        let combine = |m: &mut Vec<Vec<i128>>, p: usize, j: usize, coeffs: [i128; 4]| {
+            let [x, y, s, t] = coeffs;
+            for row in m.iter_mut() {
+                let (cp, cj) = (row[p], row[j]);
+                row[p] = x * cp + y * cj;
+                row[j] = s * cp + t * cj;
+            }
+        };
+
+        let mut pivot = 0;
+        let mut pivot_rows = Vec::new();
+        for r in 0..dims {
+            if pivot == n {
+                break;
+            }
+
+            for j in (pivot + 1)..n {
+                if a[r][j] == 0 {
+                    continue;
+                }
+
+                let (p, q) = (a[r][pivot], a[r][j]);
+                let (g, x, y) = extended_gcd(p, q);
+                let coeffs = [x, y, -q / g, p / g];
+                combine(&mut a, pivot, j, coeffs);
+                combine(&mut u, pivot, j, coeffs);
+            }
+
+            if a[r][pivot] != 0 {
+                pivot_rows.push(r);
+                pivot += 1;
+            }
+        }
+
+        // forward substitution for the pivot columns, making sure the rows
+        // without a pivot agree
+        let mut y = vec![0_i128; pivot];
+        let mut next_pivot = 0;
+        for (r, (row, target)) in a.iter().zip(machine.prize.iter()).enumerate() {
+            let partial: i128 = (0..next_pivot).map(|c| row[c] * y[c]).sum();
+            let remaining = *target as i128 - partial;
+
+            if pivot_rows.get(next_pivot) == Some(&r) {
+                if remaining % row[next_pivot] != 0 {
+                    return None;
+                }
+                y[next_pivot] = remaining / row[next_pivot];
+                next_pivot += 1;
+            } else if remaining != 0 {
+                return None;
+            }
+        }
+
+        let x0 = (0..n)
+            .map(|r| (0..pivot).map(|c| u[r][c] * y[c]).sum())
+            .collect();
+        let kernel = (pivot..n)
+            .map(|c| (0..n).map(|r| u[r][c]).collect())
+            .collect();
+
+        Some(Self {
+            x0,
+            kernel,
+            costs: machine.buttons.iter().map(|b| b.cost as i128).collect(),
+            limits: machine
+                .buttons
+                .iter()
+                .map(|b| b.limit.map(|l| l as i128))
+                .collect(),
+        })
+    }
+
+    fn free_dims(&self) -> usize {
+        self.kernel.len()
+    }
+
+    fn presses(&self, t: &[i128]) -> Vec<i128> {
+        let mut x = self.x0.clone();
+        for (col, tv) in self.kernel.iter().zip(t) {
+            for (xv, kv) in x.iter_mut().zip(col) {
+                *xv += kv * tv;
+            }
+        }
+        x
+    }
+
+    fn cost(&self, t: &[i128]) -> Option<i128> {
+        let x = self.presses(t);
+        let valid = x
+            .iter()
+            .zip(self.limits.iter())
+            .all(|(v, l)| *v >= 0 && l.map(|l| *v <= l).unwrap_or(true));
+        valid.then(|| x.iter().zip(self.costs.iter()).map(|(v, c)| v * c).sum())
+    }
+
+    /// The constraints `coeffs . t <= rhs` that keep every button within
+    /// `0..=limit` presses.
+    fn constraints(&self) -> Vec<Constraint> {
+        let mut out = Vec::new();
+        for (i, x0) in self.x0.iter().enumerate() {
+            let row: Vec<i128> = self.kernel.iter().map(|col| col[i]).collect();
+            out.push(Constraint {
+                coeffs: row.iter().map(|v| -v).collect(),
+                rhs: *x0,
+            });
+            if let Some(limit) = self.limits[i] {
+                out.push(Constraint {
+                    coeffs: row,
+                    rhs: limit - x0,
+                });
+            }
+        }
+        out
+    }
+
+    /// The cost of moving one step along each free dimension.
+    fn objective(&self) -> Vec<i128> {
+        self.kernel
+            .iter()
+            .map(|col| col.iter().zip(self.costs.iter()).map(|(k, c)| k * c).sum())
+            .collect()
+    }
+}
+
+/// `coeffs . t <= rhs`
+#[derive(Debug, Clone)]
+struct Constraint {
+    coeffs: Vec<i128>,
+    rhs: i128,
+}
+
+impl Constraint {
+    fn bound(dim: usize, dims: usize, value: i128, upper: bool) -> Self {
+        let mut coeffs = vec![0; dims];
+        let sign = if upper { 1 } else { -1 };
+        coeffs[dim] = sign;
+        Self {
+            coeffs,
+            rhs: sign * value,
+        }
+    }
+
+    fn holds(&self, t: &Vertex) -> bool {
+        let lhs: i128 = self
+            .coeffs
+            .iter()
+            .zip(t.num.iter())
+            .map(|(c, v)| c * v)
+            .sum();
+        lhs <= self.rhs * t.den
+    }
+}
+
+/// A point with rational coordinates `num / den`, where `den > 0`. We do the
+/// relaxation exactly, as the prizes are far too big for floats to tell
+/// neighboring integers apart reliably.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Vertex {
+    num: Vec<i128>,
+    den: i128,
+}
+
+struct Search<'a> {
+    lattice: &'a Lattice,
+    best: Option<(i128, Vec<i128>)>,
+    nodes: usize,
+}
+
+impl Search<'_> {
+    fn branch(&mut self, bounds: &mut Vec<Constraint>) -> anyhow::Result<()> {
+        self.nodes += 1;
+        if self.nodes > GeneralMachine::MAX_NODES {
+            bail!(
+                "Gave up after exploring {} branches",
+                GeneralMachine::MAX_NODES
+            );
+        }
+
+        let k = self.lattice.free_dims();
+        if k == 0 {
+            if let Some(cost) = self.lattice.cost(&[]) {
+                self.best = Some((cost, Vec::new()));
+            }
+            return Ok(());
+        }
+
+        let mut constraints = self.lattice.constraints();
+        constraints.extend(bounds.iter().cloned());
+        let objective = self.lattice.objective();
+        let Some(t) = relaxation(&constraints, &objective) else {
+            return Ok(());
+        };
+
+        // every integer solution has an integer cost, so if the relaxation
+        // can't beat the best we have, neither can anything under it
+        let base: i128 = self
+            .lattice
+            .x0
+            .iter()
+            .zip(self.lattice.costs.iter())
+            .map(|(x, c)| x * c)
+            .sum();
+        let value: i128 = objective.iter().zip(t.num.iter()).map(|(o, v)| o * v).sum();
+        if let Some((best, _)) = self.best.as_ref() {
+            // base + ceil(value / den)
+            if base - (-value).div_euclid(t.den) >= *best {
+                return Ok(());
+            }
+        }
+
+        let Some(dim) = (0..k).find(|i| t.num[*i] % t.den != 0) else {
+            let t: Vec<i128> = t.num.iter().map(|v| v / t.den).collect();
+            if let Some(cost) = self.lattice.cost(&t) {
+                if self.best.as_ref().map(|(b, _)| cost < *b).unwrap_or(true) {
+                    self.best = Some((cost, t));
+                }
+            }
+            return Ok(());
+        };
+
+        let floor = t.num[dim].div_euclid(t.den);
+        let branches = [
+            Constraint::bound(dim, k, floor, true),
+            Constraint::bound(dim, k, floor + 1, false),
+        ];
+        // try the side closer to the relaxation first
+        let order = if 2 * t.num[dim].rem_euclid(t.den) < t.den {
+            [0, 1]
+        } else {
+            [1, 0]
+        };
+        for i in order {
+            bounds.push(branches[i].clone());
+            self.branch(bounds)?;
+            bounds.pop();
+        }
+
+        Ok(())
+    }
+}
+
+/// Minimizes `objective . t` subject to `constraints`, by checking every
+/// vertex of the feasible region. That's fine for the handful of free
+/// dimensions and constraints we deal with. The region never contains a line,
+/// and the objective is bounded below, so the minimum is at a vertex.
+fn relaxation(constraints: &[Constraint], objective: &[i128]) -> Option<Vertex> {
+    fn visit(
+        start: usize,
+        chosen: &mut Vec<usize>,
+        constraints: &[Constraint],
+        objective: &[i128],
+        best: &mut Option<(i128, Vertex)>,
+    ) {
+        if chosen.len() == objective.len() {
+            let Some(t) = intersect(chosen.iter().map(|i| &constraints[*i])) else {
+                return;
+            };
+            if constraints.iter().all(|c| c.holds(&t)) {
+                let value: i128 = objective.iter().zip(t.num.iter()).map(|(o, v)| o * v).sum();
+                // value / t.den < best / best.den
+                let better = best
+                    .as_ref()
+                    .map(|(b, bt)| value * bt.den < b * t.den)
+                    .unwrap_or(true);
+                if better {
+                    *best = Some((value, t));
+                }
+            }
+            return;
+        }
+
+        for i in start..constraints.len() {
+            chosen.push(i);
+            visit(i + 1, chosen, constraints, objective, best);
+            chosen.pop();
+        }
+    }
+
+    let mut best = None;
+    visit(
+        0,
+        &mut Vec::with_capacity(objective.len()),
+        constraints,
+        objective,
+        &mut best,
+    );
+    best.map(|(_, t)| t)
+}
+
+/// The point where all of the given constraints are tight, if there's exactly
+/// one, via Cramer's rule.
+fn intersect<'a>(rows: impl Iterator<Item = &'a Constraint>) -> Option<Vertex> {
+    let rows: Vec<_> = rows.collect();
+    let matrix: Vec<Vec<i128>> = rows.iter().map(|c| c.coeffs.clone()).collect();
+
+    let det = determinant(matrix.clone());
+    if det == 0 {
+        return None;
+    }
+
+    let mut num: Vec<i128> = (0..rows.len())
+        .map(|col| {
+            let mut m = matrix.clone();
+            for (row, c) in m.iter_mut().zip(rows.iter()) {
+                row[col] = c.rhs;
+            }
+            determinant(m)
+        })
+        .collect();
+
+    let mut den = det;
+    if den < 0 {
+        den = -den;
+        num.iter_mut().for_each(|v| *v = -*v);
+    }
+
+    Some(Vertex { num, den })
+}
+
+/// Fraction-free (Bareiss) elimination, so everything stays an integer.
+fn determinant(mut m: Vec<Vec<i128>>) -> i128 {
+    let n = m.len();
+    let mut sign = 1;
+    let mut prev = 1;
+
+    for k in 0..n {
+        if m[k][k] == 0 {
+            let Some(swap) = ((k + 1)..n).find(|r| m[*r][k] != 0) else {
+                return 0;
+            };
+            m.swap(k, swap);
+            sign = -sign;
+        }
+
+        for i in (k + 1)..n {
+            for j in (k + 1)..n {
+                m[i][j] = (m[i][j] * m[k][k] - m[i][k] * m[k][j]) / prev;
+            }
+        }
+        prev = m[k][k];
+    }
+
+    if n == 0 {
+        1
+    } else {
+        sign * m[n - 1][n - 1]
+    }
+}
+
+/// Returns `(g, x, y)` such that `a * x + b * y = g = gcd(a, b)`, with `g >= 0`.
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_s, mut s) = (1, 0);
+    let (mut old_t, mut t) = (0, 1);
+
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_s, s) = (s, old_s - q * s);
+        (old_t, t) = (t, old_t - q * t);
+    }
+
+    if old_r < 0 {
+        (-old_r, -old_s, -old_t)
+    } else {
+        (old_r, old_s, old_t)
+    }
+}
+
+/// Narrows `lo..=hi` to the values of `k` where `0 <= v0 + k * step <= max`.
+/// Returns `None` if no value of `k` can work.
+fn constrain(lo: &mut i128, hi: &mut i128, v0: i128, step: i128, max: Option<i64>) -> Option<()> {
+    let max = max.map(|m| m as i128);
+
+    if step == 0 {
+        return (v0 >= 0 && max.map(|m| v0 <= m).unwrap_or(true)).then_some(());
+    }
+
+    let floor_div = |a: i128, b: i128| {
+        if b > 0 {
+            a.div_euclid(b)
+        } else {
+            (-a).div_euclid(-b)
+        }
+    };
+    let ceil_div = |a: i128, b: i128| -floor_div(-a, b);
+
+    if step > 0 {
+        *lo = (*lo).max(ceil_div(-v0, step));
+        if let Some(max) = max {
+            *hi = (*hi).min(floor_div(max - v0, step));
+        }
+    } else {
+        *hi = (*hi).min(floor_div(-v0, step));
+        if let Some(max) = max {
+            *lo = (*lo).max(ceil_div(max - v0, step));
+        }
+    }
+
+    Some(())
+}
+
 fn parse_machines(input: &str) -> IResult<&str, Vec<Machine>> {
     many1(parse_machine)(input)
 }
@@ -141,6 +819,222 @@ mod tests {
         assert_eq!(solution, Solution::new(35997, 82510994362072));
     }
 
+    #[test]
+    fn presses_and_limits() {
+        let machine = Machine {
+            a: Point2D::new(94, 34),
+            b: Point2D::new(22, 67),
+            prize: Point2D::new(8400, 5400),
+        };
+        assert_eq!(
+            machine.solve(&Pricing::PART_ONE),
+            Some(Presses {
+                a: 80,
+                b: 40,
+                cost: 280
+            })
+        );
+
+        let limited = Pricing {
+            limit_a: Some(79),
+            ..Pricing::PART_ONE
+        };
+        assert_eq!(machine.solve(&limited), None);
+
+        let pricing = Pricing {
+            cost_a: 1,
+            cost_b: 10,
+            ..Pricing::PART_TWO
+        };
+        assert_eq!(machine.solve(&pricing).map(|p| p.cost), Some(480));
+    }
+
+    #[test]
+    fn collinear_buttons() {
+        let machine = Machine {
+            a: Point2D::new(1, 1),
+            b: Point2D::new(2, 2),
+            prize: Point2D::new(10, 10),
+        };
+        assert_eq!(
+            machine.solve(&Pricing::PART_TWO),
+            Some(Presses {
+                a: 0,
+                b: 5,
+                cost: 5
+            })
+        );
+
+        // now a is cheaper per unit of distance
+        let mut pricing = Pricing {
+            cost_a: 1,
+            cost_b: 3,
+            ..Pricing::PART_TWO
+        };
+        assert_eq!(
+            machine.solve(&pricing),
+            Some(Presses {
+                a: 10,
+                b: 0,
+                cost: 10
+            })
+        );
+
+        pricing.limit_a = Some(4);
+        assert_eq!(
+            machine.solve(&pricing),
+            Some(Presses {
+                a: 4,
+                b: 3,
+                cost: 13
+            })
+        );
+
+        pricing.limit_b = Some(2);
+        assert_eq!(machine.solve(&pricing), None);
+
+        // off the line
+        let machine = Machine {
+            prize: Point2D::new(10, 11),
+            ..machine
+        };
+        assert_eq!(machine.solve(&Pricing::PART_TWO), None);
+
+        // gcd(3, 5) divides 7, but we can't press buttons a negative number
+        // of times
+        let machine = Machine {
+            a: Point2D::new(3, 6),
+            b: Point2D::new(5, 10),
+            prize: Point2D::new(7, 14),
+        };
+        assert_eq!(machine.solve(&Pricing::PART_TWO), None);
+        assert_eq!(
+            machine.with_offset(1).solve(&Pricing::PART_TWO),
+            None,
+            "no longer collinear"
+        );
+        let machine = Machine {
+            prize: Point2D::new(13, 26),
+            ..machine
+        };
+        assert_eq!(
+            machine.solve(&Pricing::PART_TWO),
+            Some(Presses {
+                a: 1,
+                b: 2,
+                cost: 5
+            })
+        );
+    }
+
+    #[test]
+    fn general_matches_closed_form() {
+        let input = "Button A: X+94, Y+34
+Button B: X+22, Y+67
+Prize: X=8400, Y=5400
+
+Button A: X+26, Y+66
+Button B: X+67, Y+21
+Prize: X=12748, Y=12176
+
+Button A: X+17, Y+86
+Button B: X+84, Y+37
+Prize: X=7870, Y=6450";
+        let problem = ClawContraption::from_str(input).unwrap();
+        for offset in [0, PART_TWO_OFFSET] {
+            for machine in problem.machines() {
+                let machine = machine.with_offset(offset);
+                let expected = machine.solve(&Pricing::PART_TWO).map(|p| p.cost);
+
+                // a useless third button forces us off the fast path
+                let mut general = machine.generalize(&Pricing::PART_TWO);
+                general.buttons.push(Button {
+                    delta: vec![1, 1],
+                    cost: 1,
+                    limit: Some(0),
+                });
+                let solved = general.solve().unwrap();
+                assert_eq!(solved.as_ref().map(|s| s.cost), expected);
+                if let Some(solved) = solved {
+                    assert_eq!(solved.presses[2], 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn n_buttons() {
+        let button = |delta: &[i64], cost: i64| Button {
+            delta: delta.to_vec(),
+            cost,
+            limit: None,
+        };
+
+        // three buttons in two dimensions
+        let machine = GeneralMachine {
+            buttons: vec![button(&[3, 1], 2), button(&[1, 3], 2), button(&[2, 2], 3)],
+            prize: vec![17, 19],
+        };
+        assert_eq!(
+            machine.solve().unwrap(),
+            Some(ButtonPresses {
+                presses: vec![4, 5, 0],
+                cost: 18
+            })
+        );
+
+        // change-making, where greedy doesn't work
+        let mut machine = GeneralMachine {
+            buttons: vec![button(&[1], 1), button(&[3], 1), button(&[4], 1)],
+            prize: vec![6],
+        };
+        assert_eq!(
+            machine.solve().unwrap(),
+            Some(ButtonPresses {
+                presses: vec![0, 2, 0],
+                cost: 2
+            })
+        );
+
+        machine.buttons[1].limit = Some(1);
+        assert_eq!(machine.solve().unwrap().map(|s| s.cost), Some(3));
+
+        // no combination of 4s and 6s is odd
+        let machine = GeneralMachine {
+            buttons: vec![button(&[4], 1), button(&[6], 1)],
+            prize: vec![7],
+        };
+        assert_eq!(machine.solve().unwrap(), None);
+
+        // four buttons in three dimensions, with several cheapest solutions
+        let machine = GeneralMachine {
+            buttons: vec![
+                button(&[1, 0, 2], 3),
+                button(&[0, 1, 1], 2),
+                button(&[1, 1, 0], 2),
+                button(&[2, 1, 1], 4),
+            ],
+            prize: vec![30, 40, 50],
+        };
+        let solved = machine.solve().unwrap().unwrap();
+        assert_eq!(solved.cost, 120);
+        for d in 0..3 {
+            let total: i64 = machine
+                .buttons
+                .iter()
+                .zip(solved.presses.iter())
+                .map(|(b, p)| b.delta[d] * p)
+                .sum();
+            assert_eq!(total, machine.prize[d]);
+        }
+
+        let machine = GeneralMachine {
+            buttons: vec![button(&[1, 2], 1)],
+            prize: vec![1],
+        };
+        assert!(machine.solve().is_err());
+    }
+
     #[test]
     fn example() {
         let input = "Button A: X+94, Y+34
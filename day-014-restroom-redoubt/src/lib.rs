@@ -1,35 +1,79 @@
-use std::str::FromStr;
+use std::{
+    fmt::Write,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
+use anyhow::{anyhow, bail};
 use aoc_plumbing::Problem;
-use aoc_std::{geometry::Point2D, math::ModInv};
+use aoc_std::geometry::Point2D;
 use nom::{
     bytes::complete::tag,
     character::complete,
     combinator,
     multi::separated_list1,
-    sequence::{self, preceded, separated_pair},
+    sequence::{self, preceded, separated_pair, terminated},
     IResult,
 };
 use statrs::statistics::Statistics;
 
+/// The size of the room the real puzzle input uses.
+pub const DEFAULT_WIDTH: i32 = 101;
+pub const DEFAULT_HEIGHT: i32 = 103;
+
 #[derive(Debug, Clone)]
-pub struct RestroomRedoubtGen<const N: usize, const M: usize> {
+pub struct RestroomRedoubt {
     guards: Vec<Guard>,
+    width: i32,
+    height: i32,
 }
 
-impl<const N: usize, const M: usize> FromStr for RestroomRedoubtGen<N, M> {
+impl FromStr for RestroomRedoubt {
     type Err = anyhow::Error;
 
+    /// Inputs may start with a `size=W,H` line to set the room size, otherwise
+    /// the room is the 101x103 one from the puzzle.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (_, guards) = parse_guards(s).map_err(|e| e.to_owned())?;
-        Ok(Self { guards })
+        let (rest, size) = combinator::opt(parse_size)(s).map_err(|e| e.to_owned())?;
+        let (_, guards) = parse_guards(rest).map_err(|e| e.to_owned())?;
+        let (width, height) = size.unwrap_or((DEFAULT_WIDTH, DEFAULT_HEIGHT));
+        Self {
+            guards,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+        }
+        .with_dimensions(width, height)
     }
 }
 
-impl<const N: usize, const M: usize> RestroomRedoubtGen<N, M> {
+impl RestroomRedoubt {
+    /// Uses a room of `width` by `height` tiles instead.
+    pub fn with_dimensions(mut self, width: i32, height: i32) -> anyhow::Result<Self> {
+        if width <= 0 || height <= 0 {
+            bail!("Invalid room size: {}x{}", width, height);
+        }
+        self.width = width;
+        self.height = height;
+        Ok(self)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// How many seconds until every robot is back where it started.
+    pub fn period(&self) -> i32 {
+        self.width / gcd(self.width, self.height) * self.height
+    }
+
     pub fn classify(&self, point: &Point2D<i32>) -> Option<Quadrant> {
-        let mid_x = N as i32 / 2;
-        let mid_y = M as i32 / 2;
+        let mid_x = self.width / 2;
+        let mid_y = self.height / 2;
 
         if point.x == mid_x || point.y == mid_y {
             return None;
@@ -49,7 +93,7 @@ impl<const N: usize, const M: usize> RestroomRedoubtGen<N, M> {
     pub fn safety_factor(&self, seconds: i32) -> i32 {
         let mut counts = [0; 4];
         for guard in self.guards.iter() {
-            let pos = guard.bound_position(seconds, N as i32, M as i32);
+            let pos = guard.bound_position(seconds, self.width, self.height);
             if let Some(q) = self.classify(&pos) {
                 counts[q as usize] += 1;
             }
@@ -60,20 +104,17 @@ impl<const N: usize, const M: usize> RestroomRedoubtGen<N, M> {
 
     // this is going off the observation that the variance is much smaller for
     // the configuration where the tree is present
-    pub fn tree(&self) -> i32 {
-        // precalculcate the inverse based on the width and height we're expecting
-        let inv = (N as i32).mod_inv(M as i32);
-
+    pub fn tree(&self) -> anyhow::Result<i32> {
         let mut min_x = i32::MAX;
         let mut min_x_variance = f64::MAX;
         let mut min_y = i32::MAX;
         let mut min_y_variance = f64::MAX;
 
-        for t in 0..N as i32 {
+        for t in 0..self.width {
             let v = self
                 .guards
                 .iter()
-                .map(|g| g.bounded_x(t, N as i32) as f64)
+                .map(|g| g.bounded_x(t, self.width) as f64)
                 .variance();
             if v < min_x_variance {
                 min_x_variance = v;
@@ -81,11 +122,11 @@ impl<const N: usize, const M: usize> RestroomRedoubtGen<N, M> {
             }
         }
 
-        for t in 0..M as i32 {
+        for t in 0..self.height {
             let v = self
                 .guards
                 .iter()
-                .map(|g| g.bounded_y(t, M as i32) as f64)
+                .map(|g| g.bounded_y(t, self.height) as f64)
                 .variance();
             if v < min_y_variance {
                 min_y_variance = v;
@@ -93,11 +134,130 @@ impl<const N: usize, const M: usize> RestroomRedoubtGen<N, M> {
             }
         }
 
-        min_x + (inv * (min_y - min_x)).rem_euclid(M as i32) * N as i32
+        crt(min_x, self.width, min_y, self.height).ok_or_else(|| {
+            anyhow!(
+                "No unique answer: the robots line up in x at {} (mod {}) and in y at {} (mod {}), which never happen together",
+                min_x,
+                self.width,
+                min_y,
+                self.height
+            )
+        })
+    }
+
+    /// Where every robot is after `seconds`.
+    pub fn positions(&self, seconds: i32) -> Vec<Point2D<i32>> {
+        self.guards
+            .iter()
+            .map(|g| g.bound_position(seconds, self.width, self.height))
+            .collect()
+    }
+
+    /// The number of robots on every tile after `seconds`, by row.
+    pub fn counts(&self, seconds: i32) -> Vec<Vec<u32>> {
+        let mut counts = vec![vec![0; self.width as usize]; self.height as usize];
+        for pos in self.positions(seconds) {
+            counts[pos.y as usize][pos.x as usize] += 1;
+        }
+        counts
+    }
+
+    /// Renders the room after `seconds` like the puzzle does, with the number
+    /// of robots on each tile (or `*` for more than 9).
+    pub fn render(&self, seconds: i32) -> String {
+        let mut out = String::with_capacity(((self.width + 1) * self.height) as usize);
+        for row in self.counts(seconds) {
+            for count in row {
+                out.push(match count {
+                    0 => '.',
+                    c => char::from_digit(c, 10).unwrap_or('*'),
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Renders the room after `seconds` as a plain PBM image, with a black
+    /// pixel for every tile with at least one robot.
+    pub fn render_pbm(&self, seconds: i32) -> String {
+        let mut out = String::with_capacity(16 + ((self.width * 2 + 1) * self.height) as usize);
+        // writing to a string can't fail
+        let _ = writeln!(
+            out,
+            "P1\n# {} seconds\n{} {}",
+            seconds, self.width, self.height
+        );
+        for row in self.counts(seconds) {
+            let line: Vec<_> = row.iter().map(|c| if *c > 0 { "1" } else { "0" }).collect();
+            out.push_str(&line.join(" "));
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn write_pbm(&self, seconds: i32, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        std::fs::write(path, self.render_pbm(seconds))?;
+        Ok(())
+    }
+
+    /// Writes a PBM image for each second in `frames` to `dir`, returning the
+    /// paths written.
+    pub fn export_frames(
+        &self,
+        frames: Range<i32>,
+        dir: impl AsRef<Path>,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        let mut out = Vec::with_capacity(frames.len());
+        for seconds in frames {
+            let path = dir.join(format!("frame-{:05}.pbm", seconds));
+            self.write_pbm(seconds, &path)?;
+            out.push(path);
+        }
+
+        Ok(out)
+    }
+
+    /// The sum of the Shannon entropies (in bits) of the robots' x and y
+    /// coordinates after `seconds`. A picture needs the robots bunched up in a
+    /// few rows and columns, which makes this lower.
+    pub fn entropy(&self, seconds: i32) -> f64 {
+        let mut xs = vec![0_u32; self.width as usize];
+        let mut ys = vec![0_u32; self.height as usize];
+        for pos in self.positions(seconds) {
+            xs[pos.x as usize] += 1;
+            ys[pos.y as usize] += 1;
+        }
+
+        let total = self.guards.len() as f64;
+        let h = |counts: &[u32]| -> f64 {
+            counts
+                .iter()
+                .filter(|c| **c > 0)
+                .map(|c| {
+                    let p = *c as f64 / total;
+                    -p * p.log2()
+                })
+                .sum()
+        };
+
+        h(&xs) + h(&ys)
+    }
+
+    /// The `k` frames (within one full cycle of the robots) with the lowest
+    /// entropy, lowest first, along with their scores.
+    pub fn lowest_entropy(&self, k: usize) -> Vec<(i32, f64)> {
+        let mut scores: Vec<_> = (0..self.period()).map(|t| (t, self.entropy(t))).collect();
+        scores.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        scores.truncate(k);
+        scores
     }
 }
 
-impl<const N: usize, const M: usize> Problem for RestroomRedoubtGen<N, M> {
+impl Problem for RestroomRedoubt {
     const DAY: usize = 14;
     const TITLE: &'static str = "restroom redoubt";
     const README: &'static str = include_str!("../README.md");
@@ -111,12 +271,10 @@ impl<const N: usize, const M: usize> Problem for RestroomRedoubtGen<N, M> {
     }
 
     fn part_two(&mut self) -> Result<Self::P2, Self::ProblemError> {
-        Ok(self.tree())
+        self.tree()
     }
 }
 
-pub type RestroomRedoubt = RestroomRedoubtGen<101, 103>;
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Quadrant {
     UL = 0,
@@ -151,6 +309,48 @@ impl Guard {
     }
 }
 
+/// Solves `t = a (mod m)` and `t = b (mod n)` for the smallest non-negative
+/// `t`, which is unique mod `lcm(m, n)`. `None` if there's no such `t`.
+fn crt(a: i32, m: i32, b: i32, n: i32) -> Option<i32> {
+    let (a, m, b, n) = (a as i64, m as i64, b as i64, n as i64);
+    let (g, p, _) = extended_gcd(m, n);
+    if (b - a) % g != 0 {
+        return None;
+    }
+
+    let lcm = m / g * n;
+    let t = a + m * ((b - a) / g * p).rem_euclid(n / g);
+    Some(t.rem_euclid(lcm) as i32)
+}
+
+/// Returns `(g, x, y)` with `a * x + b * y = g = gcd(a, b)`.
+fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
+    }
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn parse_size(input: &str) -> IResult<&str, (i32, i32)> {
+    terminated(
+        preceded(
+            tag("size="),
+            separated_pair(complete::i32, complete::char(','), complete::i32),
+        ),
+        complete::newline,
+    )(input)
+}
+
 fn parse_guards(input: &str) -> IResult<&str, Vec<Guard>> {
     separated_list1(complete::newline, parse_guard)(input)
 }
@@ -193,21 +393,119 @@ mod tests {
         assert_eq!(solution, Solution::new(219512160, 6398));
     }
 
-    // #[test]
-    // fn example() {
-    //     let input = "p=0,4 v=3,-3
-    // p=6,3 v=-1,-3
-    // p=10,3 v=-1,2
-    // p=2,0 v=2,-1
-    // p=0,0 v=1,3
-    // p=3,0 v=-2,-2
-    // p=7,6 v=-1,-3
-    // p=3,0 v=-1,-2
-    // p=9,3 v=2,3
-    // p=7,3 v=-1,2
-    // p=2,4 v=2,-3
-    // p=9,5 v=-3,-3";
-    //     let solution = RestroomRedoubtGen::<11, 7>::solve(input).unwrap();
-    //     assert_eq!(solution, Solution::new(12, 1002));
-    // }
+    const EXAMPLE: &str = "p=0,4 v=3,-3
+p=6,3 v=-1,-3
+p=10,3 v=-1,2
+p=2,0 v=2,-1
+p=0,0 v=1,3
+p=3,0 v=-2,-2
+p=7,6 v=-1,-3
+p=3,0 v=-1,-2
+p=9,3 v=2,3
+p=7,3 v=-1,2
+p=2,4 v=2,-3
+p=9,5 v=-3,-3";
+
+    #[test]
+    fn render() {
+        let problem = RestroomRedoubt::from_str(EXAMPLE)
+            .and_then(|p| p.with_dimensions(11, 7))
+            .unwrap();
+        assert_eq!(
+            problem.render(100),
+            "......2..1.
+...........
+1..........
+.11........
+.....1.....
+...12......
+.1....1....
+"
+        );
+        assert_eq!(problem.safety_factor(100), 12);
+
+        let pbm = problem.render_pbm(100);
+        let lines: Vec<_> = pbm.lines().collect();
+        assert_eq!(lines[0], "P1");
+        assert_eq!(lines[2], "11 7");
+        assert_eq!(lines[3], "0 0 0 0 0 0 1 0 0 1 0");
+        assert_eq!(lines.len(), 3 + 7);
+
+        let dir = std::env::temp_dir().join(format!("restroom-redoubt-{}", std::process::id()));
+        let paths = problem.export_frames(99..102, &dir).unwrap();
+        assert_eq!(paths.len(), 3);
+        assert_eq!(std::fs::read_to_string(&paths[1]).unwrap(), pbm);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lowest_entropy() {
+        // every robot ends up on (5, 3) at t = 20
+        let input = "p=5,3 v=1,1
+p=5,3 v=-2,3
+p=5,3 v=4,-1
+p=5,3 v=3,2";
+        let mut problem = RestroomRedoubt::from_str(input)
+            .and_then(|p| p.with_dimensions(11, 7))
+            .unwrap();
+        for guard in problem.guards.iter_mut() {
+            guard.origin = guard.bound_position(-20, 11, 7);
+        }
+
+        let ranked = problem.lowest_entropy(3);
+        assert_eq!(ranked.len(), 3);
+        assert_eq!(ranked[0], (20, 0.0));
+        assert!(ranked[1].1 > 0.0);
+        assert!(ranked[1].1 <= ranked[2].1);
+    }
+
+    #[test]
+    fn dimensions() {
+        let mut problem = RestroomRedoubt::from_str(&format!("size=11,7\n{}", EXAMPLE)).unwrap();
+        assert_eq!((problem.width(), problem.height()), (11, 7));
+        assert_eq!(problem.part_one().unwrap(), 12);
+
+        let problem = RestroomRedoubt::from_str(EXAMPLE).unwrap();
+        assert_eq!((problem.width(), problem.height()), (101, 103));
+        assert!(problem.with_dimensions(0, 7).is_err());
+    }
+
+    #[test]
+    fn general_crt() {
+        assert_eq!(crt(1, 4, 3, 6), Some(9));
+        assert_eq!(crt(2, 4, 3, 6), None);
+        assert_eq!(crt(37, 101, 42, 103), Some(4986));
+
+        // every robot lines up in x at t = 1 (mod 12) and in y at t = 2
+        // (mod 8), which can't both happen as the room's sides share a 4
+        let input = "p=0,0 v=1,1
+p=0,0 v=2,2
+p=0,0 v=5,3";
+        let mut problem = RestroomRedoubt::from_str(input)
+            .and_then(|p| p.with_dimensions(12, 8))
+            .unwrap();
+        for guard in problem.guards.iter_mut() {
+            let x = guard.bounded_x(-1, 12);
+            let y = guard.bounded_y(-2, 8);
+            guard.origin = Point2D::new(x, y);
+        }
+        assert!(problem.tree().is_err());
+
+        // but if they all meet at t = 20 then that's the answer
+        for guard in problem.guards.iter_mut() {
+            guard.origin = Point2D::new(0, 0);
+            guard.origin = guard.bound_position(-20, 12, 8);
+        }
+        assert_eq!(problem.period(), 24);
+        assert_eq!(problem.tree().unwrap(), 20);
+    }
+
+    #[test]
+    fn example() {
+        let input = format!("size=11,7\n{}", EXAMPLE);
+        let solution = RestroomRedoubt::solve(&input).unwrap();
+        // there's no tree in the example, so part two is just when the robots
+        // are most bunched up
+        assert_eq!(solution, Solution::new(12, 24));
+    }
 }
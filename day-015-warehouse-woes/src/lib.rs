@@ -1,18 +1,23 @@
-use std::{collections::BTreeSet, str::FromStr};
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::Entry, BinaryHeap, VecDeque},
+    fmt::Write,
+    str::FromStr,
+};
 
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
 use aoc_plumbing::Problem;
 use aoc_std::{
     collections::CharGrid,
-    directions::{BoundedCardinalNeighbors, Cardinal, Direction},
+    directions::{BoundedCardinalNeighbors, Cardinal},
     geometry::Location,
 };
+use rustc_hash::{FxHashMap, FxHashSet};
 
 #[derive(Debug, Clone)]
 pub struct WarehouseWoes {
-    grid: CharGrid,
-    wide_grid: CharGrid,
-    start: Location,
+    warehouse: Warehouse,
+    wide_warehouse: Warehouse,
     movements: Vec<Cardinal>,
 }
 
@@ -25,392 +30,772 @@ impl FromStr for WarehouseWoes {
             .split_once("\n\n")
             .ok_or_else(|| anyhow!("invalid input"))?;
 
-        let mut grid = CharGrid::from_str(left)?;
+        let warehouse = Warehouse::from_str(left)?;
+        let wide_warehouse = warehouse.scaled(2)?;
+        let movements = parse_movements(right)?;
+
+        Ok(Self {
+            warehouse,
+            wide_warehouse,
+            movements,
+        })
+    }
+}
+
+impl WarehouseWoes {
+    pub fn warehouse(&self) -> &Warehouse {
+        &self.warehouse
+    }
+
+    pub fn movements(&self) -> &[Cardinal] {
+        &self.movements
+    }
+
+    /// Step through the movements on the original map.
+    pub fn simulate(&self) -> Simulation<'_> {
+        Simulation::new(self.warehouse.clone(), &self.movements)
+    }
+
+    /// Step through the movements on the doubled-width map.
+    pub fn simulate_wide(&self) -> Simulation<'_> {
+        Simulation::new(self.wide_warehouse.clone(), &self.movements)
+    }
+
+    /// Step through the movements on the map with everything made `factor`
+    /// times as wide.
+    pub fn simulate_scaled(&self, factor: usize) -> anyhow::Result<Simulation<'_>> {
+        Ok(Simulation::new(
+            self.warehouse.scaled(factor)?,
+            &self.movements,
+        ))
+    }
+
+    pub fn rearrange(&self) -> usize {
+        self.simulate().finish().gps()
+    }
 
-        let mut start = Location::default();
+    pub fn rearrange_wide(&self) -> usize {
+        self.simulate_wide().finish().gps()
+    }
+}
+
+/// A box of any shape. Its cells are kept in reading order, along with the
+/// character each is drawn with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crate {
+    cells: Vec<Location>,
+    glyphs: Vec<char>,
+}
+
+impl Crate {
+    pub fn cells(&self) -> &[Location] {
+        &self.cells
+    }
+
+    /// The first cell in reading order, e.g. the `[` of a `[]`.
+    pub fn anchor(&self) -> Location {
+        self.cells[0]
+    }
+
+    /// The GPS coordinate of the top left corner of the box.
+    pub fn gps(&self) -> usize {
+        let row = self.cells.iter().map(|c| c.row).min().unwrap_or_default();
+        let col = self.cells.iter().map(|c| c.col).min().unwrap_or_default();
+        row * 100 + col
+    }
+}
+
+/// The state of a warehouse part way through the robot's movements.
+///
+/// Maps are made of walls (`#`), floor (`.`), the robot (`@`) and boxes:
+///
+/// * `O` is a box one tile wide.
+/// * `[`, any number of `-` then `]` is a box as wide as that, so `[]` and `[-]`
+///   are two and three tiles wide.
+/// * Any lowercase letter is a box made of every tile of that letter joined
+///   to it, which allows for tall or L-shaped boxes. Boxes touching each
+///   other need different letters.
+///
+/// Targets for [`Warehouse::solve`] are marked with `T`. A `*` is an `O` box
+/// already on a target, and a `+` is the robot standing on one. Other shapes
+/// of box can't start on targets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warehouse {
+    grid: CharGrid,
+    robot: Location,
+    crates: Vec<Crate>,
+    ids: Vec<Vec<Option<usize>>>,
+    targets: Vec<Vec<bool>>,
+}
+
+/// The robot and the anchors of each shape of box, which is all that matters
+/// when checking if we've seen a warehouse before.
+type StateKey = (Location, Vec<(usize, Location)>);
+
+/// A warehouse reached while solving, and how we got there. Only the robot
+/// and box anchors are kept, everything else is the same as where we started.
+#[derive(Debug, Clone)]
+struct Visit {
+    parent: Option<usize>,
+    movement: Cardinal,
+    robot: Location,
+    anchors: Vec<Location>,
+}
+
+impl FromStr for Warehouse {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut grid = CharGrid::from_str(s)?;
+
+        let mut robot = None;
 
         'outer: for r in 0..grid.height() {
             for c in 0..grid.width() {
-                if grid.locations[r][c] == '@' {
-                    start = Location::new(r, c);
-                    grid.locations[r][c] = '.';
-                    break 'outer;
-                }
+                let under = match grid.locations[r][c] {
+                    '@' => '.',
+                    '+' => 'T',
+                    _ => continue,
+                };
+                robot = Some(Location::new(r, c));
+                grid.locations[r][c] = under;
+                break 'outer;
             }
         }
 
-        let mut wide_grid = CharGrid::from(vec![vec!['.'; grid.width() * 2]; grid.height()]);
+        let robot = robot.ok_or_else(|| anyhow!("No robot (@) in the map"))?;
+
+        Self::new(grid, robot)
+    }
+}
+
+impl Warehouse {
+    /// How many states we're willing to explore before giving up on solving.
+    const MAX_STATES: usize = 1_000_000;
+
+    pub fn new(mut grid: CharGrid, robot: Location) -> anyhow::Result<Self> {
+        let mut crates = Vec::default();
+        let mut ids = vec![vec![None; grid.width()]; grid.height()];
+        let mut targets = vec![vec![false; grid.width()]; grid.height()];
 
         for r in 0..grid.height() {
             for c in 0..grid.width() {
-                let wc = c * 2;
-                match grid.locations[r][c] {
-                    '#' => {
-                        wide_grid.locations[r][wc + 1] = '#';
-                        wide_grid.locations[r][wc] = '#';
+                if ids[r][c].is_some() {
+                    continue;
+                }
+
+                let cells = match grid.locations[r][c] {
+                    '#' | '.' => continue,
+                    'T' => {
+                        targets[r][c] = true;
+                        continue;
                     }
-                    'O' => {
-                        wide_grid.locations[r][wc + 1] = ']';
-                        wide_grid.locations[r][wc] = '[';
+                    'O' => vec![Location::new(r, c)],
+                    '*' => {
+                        targets[r][c] = true;
+                        grid.locations[r][c] = 'O';
+                        vec![Location::new(r, c)]
                     }
-                    _ => {}
+                    '[' => {
+                        let mut cells = vec![Location::new(r, c)];
+                        let row = &grid.locations[r];
+                        let mut end = c + 1;
+                        while row.get(end) == Some(&'-') {
+                            cells.push(Location::new(r, end));
+                            end += 1;
+                        }
+                        if row.get(end) != Some(&']') {
+                            bail!("Box at ({}, {}) is missing its ]", r, c);
+                        }
+                        cells.push(Location::new(r, end));
+                        cells
+                    }
+                    ']' | '-' => bail!("Box part at ({}, {}) is missing its [", r, c),
+                    ch if ch.is_ascii_lowercase() => flood_crate(&grid, Location::new(r, c)),
+                    ch => bail!("Unknown tile {:?} at ({}, {})", ch, r, c),
+                };
+
+                for cell in cells.iter() {
+                    ids[cell.row][cell.col] = Some(crates.len());
                 }
+                let glyphs = cells.iter().map(|l| grid.locations[l.row][l.col]).collect();
+                crates.push(Crate { cells, glyphs });
             }
         }
 
-        let movements = right
-            .lines()
-            .flat_map(|line| line.chars())
-            .map(|c| match c {
-                '<' => Cardinal::West,
-                '>' => Cardinal::East,
-                '^' => Cardinal::North,
-                'v' => Cardinal::South,
-                _ => panic!("what is this {}", c),
-            })
-            .collect();
+        if !matches!(grid.get(&robot), Some('.') | Some('T')) {
+            bail!(
+                "The robot at ({}, {}) isn't on the floor",
+                robot.row,
+                robot.col
+            );
+        }
 
         Ok(Self {
             grid,
-            wide_grid,
-            start,
-            movements,
+            robot,
+            crates,
+            ids,
+            targets,
         })
     }
-}
 
-impl WarehouseWoes {
-    pub fn rearrange(&mut self) -> usize {
-        let mut pos = self.start;
+    pub fn grid(&self) -> &CharGrid {
+        &self.grid
+    }
 
-        for m in 0..self.movements.len() {
-            let dir = self.movements[m];
-            if let Some(next) = self.maybe_shift_boxes(&pos, dir) {
-                pos = next;
+    pub fn robot(&self) -> Location {
+        self.robot
+    }
+
+    pub fn crates(&self) -> &[Crate] {
+        &self.crates
+    }
+
+    /// Make everything `factor` times as wide, like the second half of the
+    /// puzzle does with a factor of 2. A box `O` becomes `[]`, `[-]`, etc.
+    pub fn scaled(&self, factor: usize) -> anyhow::Result<Self> {
+        if factor == 0 {
+            bail!("Can't scale a warehouse by 0");
+        }
+
+        let stretch = |ch: char, out: &mut String| match ch {
+            'O' if factor == 1 => out.push('O'),
+            'O' => {
+                out.push('[');
+                out.push_str(&"-".repeat(factor - 2));
+                out.push(']');
             }
+            '[' => {
+                out.push('[');
+                out.push_str(&"-".repeat(factor - 1));
+            }
+            ']' => {
+                out.push_str(&"-".repeat(factor - 1));
+                out.push(']');
+            }
+            ch => out.push_str(&ch.to_string().repeat(factor)),
+        };
+
+        let mut rows = Vec::with_capacity(self.grid.height());
+        for row in self.grid.locations.iter() {
+            let mut line = String::with_capacity(row.len() * factor);
+            for ch in row.iter() {
+                stretch(*ch, &mut line);
+            }
+            rows.push(line.chars().collect());
         }
 
-        let mut out = 0;
+        let grid = CharGrid::from(rows);
 
-        for r in 1..self.grid.height() - 1 {
-            for c in 1..self.grid.width() - 1 {
-                if self.grid.locations[r][c] == 'O' {
-                    out += r * 100 + c;
-                }
+        let mut scaled = Self::new(grid, Location::new(self.robot.row, self.robot.col * factor))?;
+
+        // boxes can be sitting on targets, which the grid doesn't show
+        for (from, to) in self.targets.iter().zip(scaled.targets.iter_mut()) {
+            for (c, target) in from.iter().enumerate() {
+                to[(c * factor)..((c + 1) * factor)].fill(*target);
             }
         }
 
-        out
+        Ok(scaled)
     }
 
-    pub fn rearrange_wide(&mut self) -> usize {
-        let mut pos = self.start;
-        pos.col *= 2;
+    /// The sum of the GPS coordinates of every box.
+    pub fn gps(&self) -> usize {
+        self.crates.iter().map(|c| c.gps()).sum()
+    }
 
-        for m in 0..self.movements.len() {
-            let dir = self.movements[m];
-            if let Some(next) = self.maybe_shift_wide_boxes(&pos, dir) {
-                pos = next;
+    /// Render the map like the puzzle does, with the robot as `@`, or `+` on a
+    /// target, and `O` boxes on targets as `*`.
+    pub fn render(&self) -> String {
+        let mut out = String::with_capacity((self.grid.width() + 1) * self.grid.height());
+        for (r, row) in self.grid.locations.iter().enumerate() {
+            for (c, ch) in row.iter().enumerate() {
+                let target = self.targets[r][c];
+                if self.robot == Location::new(r, c) {
+                    out.push(if target { '+' } else { '@' });
+                } else if *ch == 'O' && target {
+                    out.push('*');
+                } else {
+                    out.push(*ch);
+                }
             }
+            out.push('\n');
         }
+        out
+    }
 
-        let mut out = 0;
+    /// Try to move the robot one tile in `direction`, pushing any boxes in
+    /// the way. Returns the anchors of the boxes that were pushed (from before
+    /// they moved), or `None` if the robot couldn't move.
+    pub fn step(&mut self, direction: Cardinal) -> Option<Vec<Location>> {
+        let next = self.robot.cardinal_neighbor(direction)?;
+        let pushed = self.pushable(next, direction)?;
 
-        for r in 1..self.wide_grid.height() - 1 {
-            for c in 2..self.wide_grid.width() - 2 {
-                if self.wide_grid.locations[r][c] == '[' {
-                    out += r * 100 + c;
-                }
+        let anchors = pushed.iter().map(|id| self.crates[*id].anchor()).collect();
+
+        for id in pushed.iter() {
+            for cell in self.crates[*id].cells.iter() {
+                self.grid.locations[cell.row][cell.col] = self.floor(cell);
+                self.ids[cell.row][cell.col] = None;
             }
         }
 
-        out
+        for id in pushed.iter() {
+            let krate = &mut self.crates[*id];
+            for (cell, glyph) in krate.cells.iter_mut().zip(krate.glyphs.iter()) {
+                // pushable already checked these are in the grid
+                *cell = cell.cardinal_neighbor(direction).unwrap();
+                self.grid.locations[cell.row][cell.col] = *glyph;
+                self.ids[cell.row][cell.col] = Some(*id);
+            }
+        }
+
+        self.robot = next;
+        Some(anchors)
     }
 
-    fn maybe_shift_boxes(&mut self, loc: &Location, direction: Cardinal) -> Option<Location> {
-        if let Some((nloc, nch)) = self.grid.cardinal_neighbor(loc, direction) {
-            match nch {
-                '.' => return Some(nloc),
-                'O' => {
-                    if let Some(last) = self.maybe_shift_dir(&nloc, direction) {
-                        self.grid.set(&last, 'O').unwrap();
-                        self.grid.set(&nloc, '.').unwrap();
-                        return Some(nloc);
+    /// The boxes that would need to move for `loc` to be free, in the order
+    /// they'd be pushed, or `None` if something can't move.
+    fn pushable(&self, loc: Location, direction: Cardinal) -> Option<Vec<usize>> {
+        let mut pushed = Vec::default();
+        let mut stack = vec![loc];
+
+        while let Some(loc) = stack.pop() {
+            match self.ids.get(loc.row)?.get(loc.col)? {
+                Some(id) => {
+                    if pushed.contains(id) {
+                        continue;
+                    }
+                    pushed.push(*id);
+                    for cell in self.crates[*id].cells.iter().rev() {
+                        stack.push(cell.cardinal_neighbor(direction)?);
+                    }
+                }
+                None => {
+                    if self.grid.locations[loc.row][loc.col] == '#' {
+                        return None;
                     }
                 }
-                _ => return None,
             }
         }
-        None
+
+        Some(pushed)
     }
 
-    fn maybe_shift_dir(&self, loc: &Location, direction: Cardinal) -> Option<Location> {
-        if let Some((nloc, nch)) = self.grid.cardinal_neighbor(loc, direction) {
-            return match nch {
-                '.' => Some(nloc),
-                'O' => self.maybe_shift_dir(&nloc, direction),
-                _ => None,
-            };
+    fn floor(&self, loc: &Location) -> char {
+        if self.targets[loc.row][loc.col] {
+            'T'
+        } else {
+            '.'
         }
-        None
-    }
-
-    fn maybe_shift_wide_boxes(&mut self, loc: &Location, direction: Cardinal) -> Option<Location> {
-        if let Some((nloc, nch)) = self.wide_grid.cardinal_neighbor(loc, direction) {
-            match nch {
-                '.' => return Some(nloc),
-                '[' => {
-                    match direction {
-                        // easy
-                        Cardinal::East => {
-                            let mut seen = Vec::default();
-                            seen.push(nloc);
-                            if self.maybe_shift_east(&nloc, &mut seen) {
-                                for s in seen.iter().rev() {
-                                    self.wide_grid.locations[s.row][s.col + 2] = ']';
-                                    self.wide_grid.locations[s.row][s.col + 1] = '[';
-                                }
-                                self.wide_grid.locations[nloc.row][nloc.col] = '.';
-                                return Some(nloc);
-                            }
-                        }
+    }
 
-                        Cardinal::North => {
-                            let right = nloc.cardinal_neighbor(Cardinal::East).unwrap();
-                            let mut seen = BTreeSet::new();
-                            if self.maybe_shift_north(&nloc, &right, &mut seen) {
-                                for s in seen {
-                                    self.wide_grid.locations[s.row][s.col] = '.';
-                                    self.wide_grid.locations[s.row - 1][s.col] = '[';
-
-                                    self.wide_grid.locations[s.row][s.col + 1] = '.';
-                                    self.wide_grid.locations[s.row - 1][s.col + 1] = ']';
-                                }
-
-                                self.wide_grid.locations[nloc.row][nloc.col] = '.';
-                                self.wide_grid.locations[nloc.row - 1][nloc.col] = '[';
-
-                                self.wide_grid.locations[right.row][right.col] = '.';
-                                self.wide_grid.locations[right.row - 1][right.col] = ']';
-                                return Some(nloc);
-                            }
-                        }
-                        Cardinal::South => {
-                            let right = nloc.cardinal_neighbor(Cardinal::East).unwrap();
-                            let mut seen = BTreeSet::new();
-                            if self.maybe_shift_south(&nloc, &right, &mut seen) {
-                                for s in seen.iter().rev() {
-                                    self.wide_grid.locations[s.row][s.col] = '.';
-                                    self.wide_grid.locations[s.row + 1][s.col] = '[';
-
-                                    self.wide_grid.locations[s.row][s.col + 1] = '.';
-                                    self.wide_grid.locations[s.row + 1][s.col + 1] = ']';
-                                }
-
-                                self.wide_grid.locations[nloc.row][nloc.col] = '.';
-                                self.wide_grid.locations[nloc.row + 1][nloc.col] = '[';
-
-                                self.wide_grid.locations[right.row][right.col] = '.';
-                                self.wide_grid.locations[right.row + 1][right.col] = ']';
-                                return Some(nloc);
-                            }
-                        }
-                        // should ot be possible
-                        Cardinal::West => unreachable!(),
-                    }
+    /// Whether every box is sitting entirely on targets.
+    pub fn is_solved(&self) -> bool {
+        self.crates
+            .iter()
+            .all(|k| k.cells.iter().all(|c| self.targets[c.row][c.col]))
+    }
+
+    /// Find the fewest pushes that get every box onto targets, and the fewest
+    /// movements that make that many pushes, in the puzzle's `<>^v` format.
+    /// `None` if it can't be done.
+    pub fn solve(&self) -> anyhow::Result<Option<String>> {
+        let live: Vec<_> = (0..self.crates.len())
+            .map(|id| self.live_anchors(id))
+            .collect();
+        let is_live = |warehouse: &Warehouse| {
+            warehouse
+                .crates
+                .iter()
+                .zip(live.iter())
+                .all(|(k, live)| live.contains(&k.anchor()))
+        };
+
+        if !is_live(self) {
+            return Ok(None);
+        }
+
+        let shapes = self.shapes();
+
+        // every warehouse we've reached, so we can work out how we got there
+        let mut visits = vec![Visit {
+            parent: None,
+            movement: Cardinal::North,
+            robot: self.robot,
+            anchors: self.anchors(),
+        }];
+        let mut best: FxHashMap<StateKey, (usize, usize)> = FxHashMap::default();
+        best.insert(self.state_key(&shapes), (0, 0));
+
+        // ordered by pushes, then movements
+        let mut heap = BinaryHeap::from([Reverse((0, 0, 0))]);
+
+        // rather than keeping a whole warehouse for every state, put the robot
+        // and boxes back where they were in this one
+        let mut warehouse = self.clone();
+
+        while let Some(Reverse((pushes, moves, idx))) = heap.pop() {
+            warehouse.restore(visits[idx].robot, &visits[idx].anchors);
+            if best.get(&warehouse.state_key(&shapes)) != Some(&(pushes, moves)) {
+                continue;
+            }
+
+            if warehouse.is_solved() {
+                let mut path = Vec::default();
+                let mut cur = idx;
+                while let Some(parent) = visits[cur].parent {
+                    path.push(movement_char(visits[cur].movement));
+                    cur = parent;
                 }
-                ']' => {
-                    match direction {
-                        // easy
-                        Cardinal::West => {
-                            let mut seen = Vec::default();
-                            seen.push(nloc);
-                            if self.maybe_shift_west(&nloc, &mut seen) {
-                                for s in seen.iter().rev() {
-                                    self.wide_grid.locations[s.row][s.col - 2] = '[';
-                                    self.wide_grid.locations[s.row][s.col - 1] = ']';
-                                }
-                                self.wide_grid.locations[nloc.row][nloc.col] = '.';
-                                return Some(nloc);
-                            }
-                        }
+                return Ok(Some(path.into_iter().rev().collect()));
+            }
 
-                        Cardinal::North => {
-                            let left = nloc.cardinal_neighbor(Cardinal::West).unwrap();
-                            let mut seen = BTreeSet::new();
-                            if self.maybe_shift_north(&left, &nloc, &mut seen) {
-                                for s in seen {
-                                    self.wide_grid.locations[s.row][s.col] = '.';
-                                    self.wide_grid.locations[s.row - 1][s.col] = '[';
-
-                                    self.wide_grid.locations[s.row][s.col + 1] = '.';
-                                    self.wide_grid.locations[s.row - 1][s.col + 1] = ']';
-                                }
-
-                                self.wide_grid.locations[left.row][left.col] = '.';
-                                self.wide_grid.locations[left.row - 1][left.col] = '[';
-
-                                self.wide_grid.locations[nloc.row][nloc.col] = '.';
-                                self.wide_grid.locations[nloc.row - 1][nloc.col] = ']';
-                                return Some(nloc);
-                            }
-                        }
-                        Cardinal::South => {
-                            let left = nloc.cardinal_neighbor(Cardinal::West).unwrap();
-                            let mut seen = BTreeSet::new();
-                            if self.maybe_shift_south(&left, &nloc, &mut seen) {
-                                for s in seen.iter().rev() {
-                                    self.wide_grid.locations[s.row][s.col] = '.';
-                                    self.wide_grid.locations[s.row + 1][s.col] = '[';
-
-                                    self.wide_grid.locations[s.row][s.col + 1] = '.';
-                                    self.wide_grid.locations[s.row + 1][s.col + 1] = ']';
-                                }
-                                self.wide_grid.locations[left.row][left.col] = '.';
-                                self.wide_grid.locations[left.row + 1][left.col] = '[';
-
-                                self.wide_grid.locations[nloc.row][nloc.col] = '.';
-                                self.wide_grid.locations[nloc.row + 1][nloc.col] = ']';
-                                return Some(nloc);
-                            }
-                        }
-                        // should ot be possible
-                        Cardinal::East => unreachable!(),
+            for dir in [
+                Cardinal::North,
+                Cardinal::East,
+                Cardinal::South,
+                Cardinal::West,
+            ] {
+                warehouse.restore(visits[idx].robot, &visits[idx].anchors);
+                let Some(pushed) = warehouse.step(dir) else {
+                    continue;
+                };
+
+                if !pushed.is_empty() && !is_live(&warehouse) {
+                    continue;
+                }
+
+                let cost = (pushes + usize::from(!pushed.is_empty()), moves + 1);
+                match best.entry(warehouse.state_key(&shapes)) {
+                    Entry::Occupied(e) if *e.get() <= cost => continue,
+                    Entry::Occupied(mut e) => {
+                        e.insert(cost);
                     }
+                    Entry::Vacant(e) => {
+                        e.insert(cost);
+                    }
+                }
+
+                if best.len() > Self::MAX_STATES {
+                    bail!("Gave up after exploring {} states", Self::MAX_STATES);
                 }
-                _ => return None,
+
+                visits.push(Visit {
+                    parent: Some(idx),
+                    movement: dir,
+                    robot: warehouse.robot,
+                    anchors: warehouse.anchors(),
+                });
+                heap.push(Reverse((cost.0, cost.1, visits.len() - 1)));
             }
         }
-        None
+
+        Ok(None)
     }
 
-    fn maybe_shift_east(&self, loc: &Location, seen: &mut Vec<Location>) -> bool {
-        // we know these are safe because the grid is already padded
-        let nloc = loc.project(&Direction::East, 2).unwrap();
-        let nch = self.wide_grid.get(&nloc).unwrap();
+    fn anchors(&self) -> Vec<Location> {
+        self.crates.iter().map(|k| k.anchor()).collect()
+    }
 
-        match nch {
-            '.' => true,
-            '[' => {
-                seen.push(nloc);
-                self.maybe_shift_east(&nloc, seen)
+    /// Put the robot and every box back where they were, going by the
+    /// anchors of the boxes.
+    fn restore(&mut self, robot: Location, anchors: &[Location]) {
+        for id in 0..self.crates.len() {
+            for cell in self.crates[id].cells.iter() {
+                self.grid.locations[cell.row][cell.col] = self.floor(cell);
+                self.ids[cell.row][cell.col] = None;
             }
-            _ => false,
         }
-    }
 
-    fn maybe_shift_west(&self, loc: &Location, seen: &mut Vec<Location>) -> bool {
-        // we know these are safe because the grid is already padded
-        let nloc = loc.project(&Direction::West, 2).unwrap();
-        let nch = self.wide_grid.get(&nloc).unwrap();
-
-        match nch {
-            '.' => true,
-            ']' => {
-                seen.push(nloc);
-                self.maybe_shift_west(&nloc, seen)
+        for (id, (krate, anchor)) in self.crates.iter_mut().zip(anchors.iter()).enumerate() {
+            let (dr, dc) = (
+                anchor.row as isize - krate.anchor().row as isize,
+                anchor.col as isize - krate.anchor().col as isize,
+            );
+            for (cell, glyph) in krate.cells.iter_mut().zip(krate.glyphs.iter()) {
+                // these came from pushing boxes around, so they're in the grid
+                *cell = Location::new(
+                    cell.row.wrapping_add_signed(dr),
+                    cell.col.wrapping_add_signed(dc),
+                );
+                self.grid.locations[cell.row][cell.col] = *glyph;
+                self.ids[cell.row][cell.col] = Some(id);
             }
-            _ => false,
         }
+
+        self.robot = robot;
     }
 
-    fn maybe_shift_north(
-        &self,
-        left: &Location,
-        right: &Location,
-        seen: &mut BTreeSet<Location>,
-    ) -> bool {
-        if let (Some((l_loc, l_ch)), Some((r_loc, r_ch))) = (
-            self.wide_grid.cardinal_neighbor(left, Cardinal::North),
-            self.wide_grid.cardinal_neighbor(right, Cardinal::North),
-        ) {
-            return match (l_ch, r_ch) {
-                ('.', '.') => true,
-                ('[', ']') => !seen.insert(l_loc) || self.maybe_shift_north(&l_loc, &r_loc, seen),
-                (']', '.') => {
-                    let left = l_loc.cardinal_neighbor(Cardinal::West).unwrap();
-                    !seen.insert(left) || self.maybe_shift_north(&left, &l_loc, seen)
-                }
-                ('.', '[') => {
-                    !seen.insert(r_loc)
-                        || self.maybe_shift_north(
-                            &r_loc,
-                            &r_loc.cardinal_neighbor(Cardinal::East).unwrap(),
-                            seen,
-                        )
-                }
-                (']', '[') => {
-                    let left = l_loc.cardinal_neighbor(Cardinal::West).unwrap();
+    /// Boxes with the same shape and glyphs can be swapped without changing
+    /// anything, so number each distinct shape.
+    fn shapes(&self) -> Vec<usize> {
+        let mut distinct = Vec::default();
+        self.crates
+            .iter()
+            .map(|k| {
+                let shape = (offsets(k), k.glyphs.as_slice());
+                distinct
+                    .iter()
+                    .position(|s| *s == shape)
+                    .unwrap_or_else(|| {
+                        distinct.push(shape);
+                        distinct.len() - 1
+                    })
+            })
+            .collect()
+    }
+
+    fn state_key(&self, shapes: &[usize]) -> StateKey {
+        let mut anchors: Vec<_> = shapes
+            .iter()
+            .zip(self.crates.iter())
+            .map(|(shape, k)| (*shape, k.anchor()))
+            .collect();
+        anchors.sort_unstable();
+        (self.robot, anchors)
+    }
 
-                    if seen.insert(left) && !self.maybe_shift_north(&left, &l_loc, seen) {
-                        return false;
+    /// Every anchor the given box could be pushed onto targets from, if
+    /// nothing else were in the way. Anywhere else is a deadlock, like a
+    /// corner or along a wall without any targets.
+    ///
+    /// This works backwards from the targets, pulling the box everywhere it
+    /// could have been pushed from.
+    fn live_anchors(&self, id: usize) -> FxHashSet<Location> {
+        let offsets = offsets(&self.crates[id]);
+        let place = |anchor: &Location| -> Option<Vec<Location>> {
+            offsets
+                .iter()
+                .map(|(dr, dc)| {
+                    let loc = Location::new(
+                        anchor.row.checked_add_signed(*dr)?,
+                        anchor.col.checked_add_signed(*dc)?,
+                    );
+                    match self.grid.get(&loc) {
+                        Some('#') | None => None,
+                        _ => Some(loc),
                     }
+                })
+                .collect()
+        };
 
-                    if seen.insert(r_loc) {
-                        self.maybe_shift_north(
-                            &r_loc,
-                            &r_loc.cardinal_neighbor(Cardinal::East).unwrap(),
-                            seen,
-                        )
-                    } else {
-                        true
+        let mut live = FxHashSet::default();
+        let mut queue = VecDeque::default();
+        for r in 0..self.grid.height() {
+            for c in 0..self.grid.width() {
+                let anchor = Location::new(r, c);
+                if let Some(cells) = place(&anchor) {
+                    if cells.iter().all(|l| self.targets[l.row][l.col]) {
+                        live.insert(anchor);
+                        queue.push_back(anchor);
                     }
                 }
-                _ => false,
-            };
+            }
         }
-        false
-    }
-
-    fn maybe_shift_south(
-        &self,
-        left: &Location,
-        right: &Location,
-        seen: &mut BTreeSet<Location>,
-    ) -> bool {
-        if let (Some((l_loc, l_ch)), Some((r_loc, r_ch))) = (
-            self.wide_grid.cardinal_neighbor(left, Cardinal::South),
-            self.wide_grid.cardinal_neighbor(right, Cardinal::South),
-        ) {
-            return match (l_ch, r_ch) {
-                ('.', '.') => true,
-                ('[', ']') => !seen.insert(l_loc) || self.maybe_shift_south(&l_loc, &r_loc, seen),
-                (']', '.') => {
-                    let left = l_loc.cardinal_neighbor(Cardinal::West).unwrap();
-                    !seen.insert(left) || self.maybe_shift_south(&left, &l_loc, seen)
-                }
-                ('.', '[') => {
-                    !seen.insert(r_loc)
-                        || self.maybe_shift_south(
-                            &r_loc,
-                            &r_loc.cardinal_neighbor(Cardinal::East).unwrap(),
-                            seen,
-                        )
+
+        while let Some(anchor) = queue.pop_front() {
+            for dir in [
+                Cardinal::North,
+                Cardinal::East,
+                Cardinal::South,
+                Cardinal::West,
+            ] {
+                // the box was pushed here in `dir` from `prev`
+                let Some(prev) = anchor.cardinal_neighbor(dir.opposite()) else {
+                    continue;
+                };
+                if live.contains(&prev) {
+                    continue;
                 }
-                (']', '[') => {
-                    let left = l_loc.cardinal_neighbor(Cardinal::West).unwrap();
+                let Some(cells) = place(&prev) else {
+                    continue;
+                };
 
-                    if seen.insert(left) && !self.maybe_shift_south(&left, &l_loc, seen) {
-                        return false;
-                    }
+                // and the robot had to be behind it somewhere
+                let pushable = cells.iter().any(|cell| {
+                    cell.cardinal_neighbor(dir.opposite())
+                        .filter(|behind| !cells.contains(behind))
+                        .and_then(|behind| self.grid.get(&behind))
+                        .is_some_and(|ch| *ch != '#')
+                });
 
-                    if seen.insert(r_loc) {
-                        self.maybe_shift_south(
-                            &r_loc,
-                            &r_loc.cardinal_neighbor(Cardinal::East).unwrap(),
-                            seen,
-                        )
-                    } else {
-                        true
-                    }
+                if pushable {
+                    live.insert(prev);
+                    queue.push_back(prev);
                 }
-                _ => false,
-            };
+            }
+        }
+
+        live
+    }
+}
+
+/// Where each cell of a box is relative to its anchor.
+fn offsets(krate: &Crate) -> Vec<(isize, isize)> {
+    let anchor = krate.anchor();
+    krate
+        .cells
+        .iter()
+        .map(|c| {
+            (
+                c.row as isize - anchor.row as isize,
+                c.col as isize - anchor.col as isize,
+            )
+        })
+        .collect()
+}
+
+/// Every tile joined to `start` with the same letter, in reading order.
+fn flood_crate(grid: &CharGrid, start: Location) -> Vec<Location> {
+    let glyph = grid.locations[start.row][start.col];
+    let mut cells = vec![start];
+    let mut stack = vec![start];
+
+    while let Some(loc) = stack.pop() {
+        for (_, next, ch) in grid.cardinal_neighbors(&loc) {
+            if *ch == glyph && !cells.contains(&next) {
+                cells.push(next);
+                stack.push(next);
+            }
+        }
+    }
+
+    cells.sort();
+    cells
+}
+
+/// A single movement of the robot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// Which movement this was, counting from 0.
+    pub index: usize,
+    pub movement: Cardinal,
+    /// Whether the robot actually moved.
+    pub moved: bool,
+    /// Where the anchors of the pushed boxes were before the move.
+    pub pushed: Vec<Location>,
+    /// The warehouse after the move.
+    pub warehouse: Warehouse,
+}
+
+impl Step {
+    /// Render the warehouse after this step, with a heading for the move and,
+    /// if `diff` is set, where each pushed box went.
+    pub fn render(&self, diff: bool) -> String {
+        let mut out = String::new();
+        // writing to a string can't fail
+        let _ = writeln!(
+            out,
+            "Move {} {}:",
+            self.index + 1,
+            movement_char(self.movement)
+        );
+        out.push_str(&self.warehouse.render());
+
+        if diff {
+            if !self.moved {
+                out.push_str("blocked\n");
+            }
+            for from in self.pushed.iter() {
+                // pushed boxes always have somewhere to go
+                let to = from.cardinal_neighbor(self.movement).unwrap();
+                let _ = writeln!(
+                    out,
+                    "box ({}, {}) -> ({}, {})",
+                    from.row, from.col, to.row, to.col
+                );
+            }
+        }
+
+        out
+    }
+}
+
+/// Steps the robot through its movements one at a time, yielding the state of
+/// the warehouse after each.
+#[derive(Debug, Clone)]
+pub struct Simulation<'a> {
+    warehouse: Warehouse,
+    movements: &'a [Cardinal],
+    next: usize,
+}
+
+impl<'a> Simulation<'a> {
+    pub fn new(warehouse: Warehouse, movements: &'a [Cardinal]) -> Self {
+        Self {
+            warehouse,
+            movements,
+            next: 0,
         }
-        false
+    }
+
+    /// The warehouse as of the last step.
+    pub fn warehouse(&self) -> &Warehouse {
+        &self.warehouse
+    }
+
+    /// Run the remaining movements without recording any of the steps.
+    pub fn finish(mut self) -> Warehouse {
+        for dir in self.movements[self.next..].iter() {
+            self.warehouse.step(*dir);
+        }
+        self.next = self.movements.len();
+        self.warehouse
     }
 }
 
+impl Iterator for Simulation<'_> {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let movement = *self.movements.get(self.next)?;
+        let pushed = self.warehouse.step(movement);
+        let step = Step {
+            index: self.next,
+            movement,
+            moved: pushed.is_some(),
+            pushed: pushed.unwrap_or_default(),
+            warehouse: self.warehouse.clone(),
+        };
+        self.next += 1;
+        Some(step)
+    }
+}
+
+pub fn movement_char(movement: Cardinal) -> char {
+    match movement {
+        Cardinal::West => '<',
+        Cardinal::East => '>',
+        Cardinal::North => '^',
+        Cardinal::South => 'v',
+    }
+}
+
+fn parse_movements(input: &str) -> anyhow::Result<Vec<Cardinal>> {
+    let mut movements = Vec::with_capacity(input.len());
+
+    for (row, line) in input.lines().enumerate() {
+        for (col, ch) in line.chars().enumerate() {
+            movements.push(match ch {
+                '<' => Cardinal::West,
+                '>' => Cardinal::East,
+                '^' => Cardinal::North,
+                'v' => Cardinal::South,
+                _ => bail!(
+                    "Invalid movement {:?} at line {}, column {} of the movements",
+                    ch,
+                    row + 1,
+                    col + 1
+                ),
+            });
+        }
+    }
+
+    Ok(movements)
+}
+
 impl Problem for WarehouseWoes {
     const DAY: usize = 15;
     const TITLE: &'static str = "warehouse woes";
@@ -468,6 +853,253 @@ vvv<<^>^v^^><<>>><>^<<><^vv^^<>vvv<>><^^v>^>vv<>v<<<<v<^v>^<^^>>>^<v<v
 v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^";
         let solution = WarehouseWoes::solve(input).unwrap();
         assert_eq!(solution, Solution::new(10092, 9021));
+
+        let problem = WarehouseWoes::from_str(input).unwrap();
+        let scaled = problem.simulate_scaled(3).unwrap().finish();
+        assert_eq!(scaled.gps(), 8512);
+        assert_eq!(scaled.crates()[0].cells().len(), 3);
+        assert_eq!(problem.simulate_scaled(4).unwrap().finish().gps(), 9414);
+        assert_eq!(
+            problem.warehouse().scaled(4).unwrap(),
+            problem.warehouse().scaled(2).unwrap().scaled(2).unwrap()
+        );
+    }
+
+    #[test]
+    fn simulation() {
+        let input = "########
+#..O.O.#
+##@.O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########
+
+<^^>>>vv<v>>v<<";
+        let problem = WarehouseWoes::from_str(input).unwrap();
+        let steps: Vec<_> = problem.simulate().collect();
+        assert_eq!(steps.len(), 15);
+        assert!(!steps[0].moved);
+        assert!(steps[1].moved && steps[1].pushed.is_empty());
+
+        let step = &steps[4];
+        assert_eq!(step.warehouse.robot(), Location::new(1, 4));
+        assert_eq!(step.pushed, vec![Location::new(1, 4), Location::new(1, 5)]);
+        assert_eq!(
+            step.render(true),
+            "Move 5 >:
+########
+#...@OO#
+##..O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########
+box (1, 4) -> (1, 5)
+box (1, 5) -> (1, 6)
+"
+        );
+
+        assert_eq!(steps[14].warehouse.gps(), 2028);
+        assert_eq!(problem.rearrange(), 2028);
+    }
+
+    #[test]
+    fn simulation_wide() {
+        let input = "#######
+#...#.#
+#.....#
+#..OO@#
+#..O..#
+#.....#
+#######
+
+<vv<<^^<<^^";
+        let problem = WarehouseWoes::from_str(input).unwrap();
+        let mut sim = problem.simulate_wide();
+        let step = sim.next().unwrap();
+        assert_eq!(step.pushed, vec![Location::new(3, 8), Location::new(3, 6)]);
+        assert_eq!(
+            step.warehouse.render(),
+            "##############
+##......##..##
+##..........##
+##...[][]@..##
+##....[]....##
+##..........##
+##############
+"
+        );
+        assert_eq!(
+            sim.finish().render(),
+            "##############
+##...[].##..##
+##...@.[]...##
+##....[]....##
+##..........##
+##..........##
+##############
+"
+        );
+    }
+
+    #[test]
+    fn shapes() {
+        let mut warehouse = Warehouse::from_str(
+            "#########
+#.......#
+#.aa....#
+#..a[-].#
+#..@....#
+#########",
+        )
+        .unwrap();
+        assert_eq!(warehouse.crates().len(), 2);
+        assert_eq!(warehouse.gps(), 202 + 304);
+
+        for (dir, moved) in [
+            (Cardinal::North, true),
+            (Cardinal::East, true),
+            (Cardinal::East, false),
+            (Cardinal::North, true),
+            (Cardinal::West, true),
+        ] {
+            assert_eq!(warehouse.step(dir).is_some(), moved);
+        }
+
+        assert_eq!(
+            warehouse.render(),
+            "#########
+#aa.....#
+#.a@....#
+#....[-]#
+#.......#
+#########
+"
+        );
+        assert_eq!(warehouse.gps(), 101 + 305);
+
+        // the L can't fit past the wall
+        assert_eq!(warehouse.step(Cardinal::West), None);
+    }
+
+    #[test]
+    fn solve() {
+        let warehouse = Warehouse::from_str(
+            "#######
+#.....#
+#.O.T.#
+#.@...#
+#######",
+        )
+        .unwrap();
+        assert_eq!(warehouse.solve().unwrap().as_deref(), Some("<^>>"));
+
+        // the same level but twice as wide
+        let wide = warehouse.scaled(2).unwrap();
+        let moves = wide.solve().unwrap().unwrap();
+        assert_eq!(moves, "<^>>>>");
+        let movements = parse_movements(&moves).unwrap();
+        let solved = Simulation::new(wide, &movements).finish();
+        assert!(solved.is_solved());
+        assert_eq!(solved.render().lines().nth(2), Some("##.....@[]..##"));
+
+        // stuck in a corner
+        let warehouse = Warehouse::from_str(
+            "#####
+#O..#
+#.@T#
+#####",
+        )
+        .unwrap();
+        assert_eq!(warehouse.solve().unwrap(), None);
+
+        // stuck against a wall without any targets on it
+        let warehouse = Warehouse::from_str(
+            "######
+#.O..#
+#..@.#
+#.T..#
+######",
+        )
+        .unwrap();
+        assert_eq!(warehouse.solve().unwrap(), None);
+
+        // two boxes pushed along a wall, which is fine if there are targets
+        let warehouse = Warehouse::from_str(
+            "########
+#TT.OO@#
+#......#
+########",
+        )
+        .unwrap();
+        assert_eq!(warehouse.solve().unwrap().as_deref(), Some("<<<"));
+
+        // going the long way round means fewer pushes
+        let warehouse = Warehouse::from_str(
+            "########
+#.T....#
+#..#...#
+#@O....#
+##.#..##
+#....###
+########",
+        )
+        .unwrap();
+        let pushes = |moves: &str| {
+            let mut warehouse = warehouse.clone();
+            let mut pushes = 0;
+            for dir in parse_movements(moves).unwrap() {
+                if !warehouse.step(dir).unwrap().is_empty() {
+                    pushes += 1;
+                }
+            }
+            assert!(warehouse.is_solved());
+            pushes
+        };
+        let moves = warehouse.solve().unwrap().unwrap();
+        assert_eq!(moves, "^^>>>vvvv<<^^^");
+        assert_eq!(pushes(&moves), 2);
+        assert_eq!(pushes("^>v>>vv<<^^^"), 4);
+    }
+
+    #[test]
+    fn targets() {
+        let input = "#######
+#+.*..#
+#...O.#
+#..T..#
+#######";
+        let warehouse = Warehouse::from_str(input).unwrap();
+        assert_eq!(warehouse.render().trim_end(), input);
+        assert!(!warehouse.is_solved());
+
+        let moves = warehouse.solve().unwrap().unwrap();
+        let movements = parse_movements(&moves).unwrap();
+        let solved = Simulation::new(warehouse.clone(), &movements).finish();
+        assert!(solved.is_solved());
+        assert_eq!(solved.render().lines().nth(3), Some("#..*@.#"));
+
+        // targets stay put when the warehouse gets wider
+        let wide = warehouse.scaled(2).unwrap();
+        assert_eq!(wide.render().lines().nth(1), Some("##+T..[]....##"));
+        assert_eq!(wide.render().lines().nth(3), Some("##....TT....##"));
+        assert!(wide.solve().unwrap().is_some());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = WarehouseWoes::from_str("###\n#@#\n###\n\n<>\n^x").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid movement 'x' at line 2, column 2 of the movements"
+        );
+        assert!(WarehouseWoes::from_str("###\n#.#\n###\n\n<>").is_err());
+        assert!(Warehouse::from_str("#####\n#@[-#\n#####").is_err());
+        assert!(Warehouse::from_str("#####\n#@-]#\n#####").is_err());
+        assert!(Warehouse::from_str("###\n#@?\n###").is_err());
     }
 
     // #[test]